@@ -0,0 +1,45 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Relays discover and resolve queries to the consoles' subnet.
+//!
+//! Usage: `relay <broadcast> [forward-from] [listen-on]`
+
+extern crate xbdm;
+
+use std::env;
+use std::net::Ipv4Addr;
+use std::process;
+
+fn parse_arg(arg: Option<String>, what: &str) -> Option<Ipv4Addr> {
+    arg.map(|s| s.parse().unwrap_or_else(|_| {
+        eprintln!("invalid {} address: {}", what, s);
+        process::exit(2);
+    }))
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let broadcast = match parse_arg(args.next(), "broadcast") {
+        Some(ip) => ip,
+        None => {
+            eprintln!("usage: relay <broadcast> [forward-from] [listen-on]");
+            process::exit(2);
+        },
+    };
+    let mut relay = xbdm::Relay::new(broadcast);
+    if let Some(ip) = parse_arg(args.next(), "forward-from") {
+        relay = relay.forward_from(ip);
+    }
+    if let Some(ip) = parse_arg(args.next(), "listen-on") {
+        relay = relay.listen_on(ip);
+    }
+    if let Err(e) = relay.run() {
+        eprintln!("relay failed: {}", e);
+        process::exit(1);
+    }
+}
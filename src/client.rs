@@ -11,8 +11,16 @@ use std::net::{TcpStream, ToSocketAddrs};
 
 use bufstream::BufStream;
 
+use command::Command;
 use status::StatusCode;
 use error::{Error, Result};
+use resolver::default_console;
+use xbox::{Platform, Xbox};
+
+/// Number of commands [`Client::run_batch`] sends before reading a response.
+///
+/// [`Client::run_batch`]: struct.Client.html#method.run_batch
+const PIPELINE_DEPTH: usize = 32;
 
 fn send_command<W: Write>(writer: &mut W, command: &str) -> Result<()> {
     writer.write_all(command.as_bytes())
@@ -21,41 +29,59 @@ fn send_command<W: Write>(writer: &mut W, command: &str) -> Result<()> {
         .map_err(|e| Error::io(e, command))
 }
 
+fn write_commands<W: Write>(writer: &mut W, commands: &[String]) -> Result<()> {
+    for command in commands {
+        writer.write_all(command.as_bytes())
+            .and_then(|_| writer.write_all("\r\n".as_bytes()))
+            .map_err(|e| Error::io(e, &command[..]))?;
+    }
+    writer.flush().map_err(|e| Error::io(e, commands.last().map_or("", |c| &c[..])))
+}
+
 fn read_response<R, E>(reader: &mut R, expect: E, command: &str)
     -> Result<(StatusCode, String)>
     where R: io::BufRead, E: IntoIterator<Item=StatusCode>
 {
-    let mut line = String::new();
-    match reader.read_line(&mut line) {
-        Ok(0) |
-        Ok(_) if !line.ends_with("\n") => return Err(Error::io_custom(
-            io::ErrorKind::UnexpectedEof, "did not receive a line", command)),
-        Err(e) => return Err(Error::io(e, command)),
-        _ => {},
-    }
+    let expect = expect.into_iter().collect::<Vec<_>>();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) |
+            Ok(_) if !line.ends_with("\n") => return Err(Error::io_custom(
+                io::ErrorKind::UnexpectedEof, "did not receive a line", command)),
+            Err(e) => return Err(Error::io(e, command)),
+            _ => {},
+        }
 
-    line.pop();
-    if line.ends_with("\r") {
         line.pop();
-    }
+        if line.ends_with("\r") {
+            line.pop();
+        }
 
-    if line.len() < 5 {
-        return Err(Error::bad_response("too short", command));
-    }
+        if line.len() < 5 {
+            return Err(Error::bad_response("too short", command).with_status_line(line));
+        }
 
-    let code = StatusCode::from_u16(line[0..3].parse().map_err(|_| {
-        Error::bad_response("invalid status code", command)
-    })?);
+        let code = match line[0..3].parse() {
+            Ok(n) => StatusCode::from_u16(n),
+            Err(_) => return Err(Error::bad_response("invalid status code", command)
+                .with_status_line(line)),
+        };
 
-    let message = line[5..].to_owned();
+        let message = line[5..].to_owned();
 
-    if code.is_failure() {
-        Err(Error::command_failed(code, message, command))
-    } else if expect.into_iter().any(|c| code == c) {
-        Ok((code, message))
-    } else {
-        Err(Error::bad_response(
-            format!("unexpected response: {}- {}", code, message), command))
+        return if code.is_failure() {
+            Err(Error::command_failed(code, message, command).with_status_line(line))
+        } else if expect.contains(&code) {
+            Ok((code, message))
+        } else if code.is_informational() {
+            // The final response follows.
+            continue;
+        } else {
+            Err(Error::bad_response(
+                format!("unexpected response: {}- {}", code.to_u16(), message), command)
+                .with_status_line(line))
+        };
     }
 }
 
@@ -84,26 +110,148 @@ impl<S: BufRead + Write> Stream<S> {
 #[derive(Debug)]
 pub struct Client {
     stream: Stream<BufStream<TcpStream>>,
+    platform: Option<Platform>,
+    /// Set once the console has rejected `getmemex`, so that later reads go
+    /// straight to `getmem`.
+    pub(crate) getmemex_unsupported: bool,
 }
 
 impl Client {
     pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Client> {
-        let mut stream = BufStream::new(TcpStream::connect(addr)
-            .map_err(|e| Error::io(e, "connect"))?);
+        let tcp = TcpStream::connect(addr).map_err(|e| Error::io(e, "connect"))?;
+        let platform = tcp.peer_addr().ok().and_then(|a| Platform::from_port(a.port()));
+        let mut stream = BufStream::new(tcp);
         read_response(&mut stream, StatusCode::Connected, "connect")?;
-        Ok(Client { stream: Stream::Raw(stream) })
+        Ok(Client { stream: Stream::Raw(stream), platform, getmemex_unsupported: false })
+    }
+
+    /// Connects to `xbox`, taking its platform from the `Xbox` rather than
+    /// the port, so that it is known even if the port has been remapped.
+    pub fn connect_xbox(xbox: &Xbox) -> Result<Client> {
+        let mut client = Client::connect(xbox.socket_addr())?;
+        client.platform = Some(xbox.platform());
+        Ok(client)
+    }
+
+    /// The platform of the connected console, if known. This is inferred from
+    /// the port number connected to, or taken from the `Xbox` passed to
+    /// [`connect_xbox`].
+    ///
+    /// [`connect_xbox`]: #method.connect_xbox
+    pub fn platform(&self) -> Option<Platform> { self.platform }
+
+    /// Overrides the platform of the connected console, such as when its port
+    /// has been remapped.
+    pub fn set_platform(&mut self, platform: Option<Platform>) {
+        self.platform = platform;
+    }
+
+    /// Connects to the console returned by [`default_console`].
+    ///
+    /// [`default_console`]: fn.default_console.html
+    pub fn connect_default() -> Result<Client> {
+        Client::connect_xbox(&default_console().map_err(|e| Error::io(e, "connect"))?)
     }
 
     pub fn execute<'a, E>(&'a mut self, expect: E, command: &'a str)
-        -> Result<Execute>
+        -> Result<Execute<'a>>
         where E: IntoIterator<Item=StatusCode>
     {
-        let (code, message) = if let Stream::Raw(ref mut s) = self.stream {
-            send_command(s, command)?;
-            read_response(s, expect, command)?
-        } else {
-            unreachable!()
-        };
+        send_command(self.raw(command)?, command)?;
+        self.receive(expect, command)
+    }
+
+    /// Runs a typed command and decodes its response.
+    pub fn run<C: Command>(&mut self, cmd: C) -> Result<C::Output> {
+        cmd.validate()?;
+        let command = cmd.encode();
+        send_command(self.raw(&command)?, &command)?;
+        self.decode(cmd, &command)
+    }
+
+    /// Runs a batch of typed commands, sending them ahead of their responses
+    /// so that the batch is not bound by round trips.
+    ///
+    /// A command answered with `SendBinaryData` is sent last in its group:
+    /// the commands after it are only sent once its data has been, so that
+    /// the console does not take them for binary data.
+    ///
+    /// The results are in the same order as `commands`. If a command fails
+    /// with a [fatal] error, the connection is no longer usable and every
+    /// command after it fails with `ConnectionAborted`. Those commands may
+    /// already have been sent, in which case the console may have run them.
+    ///
+    /// [fatal]: struct.Error.html#method.is_fatal
+    pub fn run_batch<I>(&mut self, commands: I) -> Vec<Result<<I::Item as Command>::Output>>
+        where I: IntoIterator, I::Item: Command
+    {
+        let commands = commands.into_iter().collect::<Vec<_>>();
+        let lines = commands.iter().map(|c| c.encode()).collect::<Vec<_>>();
+        let sends_data = commands.iter()
+            .map(|c| c.expect().contains(&StatusCode::SendBinaryData))
+            .collect::<Vec<_>>();
+        let mut invalid = commands.iter().map(|c| c.validate().err()).collect::<Vec<_>>();
+        let mut results = Vec::with_capacity(commands.len());
+        let mut sent = 0;
+        let mut aborted = false;
+        for (i, cmd) in commands.into_iter().enumerate() {
+            if let Some(e) = invalid[i].take() {
+                results.push(Err(e));
+                continue;
+            }
+            if aborted {
+                let desc = if i < sent {
+                    "an earlier command in the batch failed; this command was sent and \
+                     may have been run"
+                } else {
+                    "an earlier command in the batch failed; this command was not sent"
+                };
+                results.push(Err(Error::io_custom(io::ErrorKind::ConnectionAborted,
+                                                  desc, &lines[i][..])));
+                continue;
+            }
+            if sent <= i {
+                // Keep a bounded number of commands in flight so that neither
+                // side blocks writing while the other is not reading.
+                let limit = ::std::cmp::min(i + PIPELINE_DEPTH, lines.len());
+                let end = (i..limit).find(|&j| sends_data[j]).map_or(limit, |j| j + 1);
+                let batch = (i..end).filter(|&j| invalid[j].is_none())
+                    .map(|j| lines[j].clone())
+                    .collect::<Vec<_>>();
+                let written = self.raw(&lines[i])
+                    .and_then(|raw| write_commands(raw, &batch));
+                if let Err(e) = written {
+                    aborted = true;
+                    results.push(Err(e));
+                    continue;
+                }
+                sent = end;
+            }
+            // If a response could not be skipped after a decode error, this
+            // is the fatal error from finishing it, since the responses that
+            // follow can no longer be told apart.
+            let result = self.decode(cmd, &lines[i]);
+            aborted = result.as_ref().err().is_some_and(Error::is_fatal);
+            results.push(result);
+        }
+        results
+    }
+
+    /// The underlying stream, or an error if an earlier `Execute` was
+    /// dropped without being finished and left a response partly read.
+    fn raw(&mut self, command: &str) -> Result<&mut BufStream<TcpStream>> {
+        match self.stream {
+            Stream::Raw(ref mut s) => Ok(s),
+            _ => Err(Error::io_custom(io::ErrorKind::BrokenPipe,
+                "an earlier response was not finished; reconnect to continue", command)),
+        }
+    }
+
+    fn receive<'a, E>(&'a mut self, expect: E, command: &'a str)
+        -> Result<Execute<'a>>
+        where E: IntoIterator<Item=StatusCode>
+    {
+        let (code, message) = read_response(self.raw(command)?, expect, command)?;
 
         if code == StatusCode::MultilineResponseFollows {
             let mut stream = Stream::None;
@@ -113,11 +261,24 @@ impl Client {
 
         Ok(Execute {
             client: self,
-            command: command,
-            code: code,
-            message: message,
+            command,
+            code,
+            message,
         })
     }
+
+    fn decode<C: Command>(&mut self, cmd: C, command: &str) -> Result<C::Output> {
+        let mut exec = self.receive(cmd.expect().iter().cloned(), command)?;
+        match cmd.decode(&mut exec) {
+            Ok(output) => exec.finish().map(|_| output),
+            Err(e) => {
+                // Keep the connection usable if the response can be skipped,
+                // and otherwise report that it is not.
+                exec.finish()?;
+                Err(e)
+            },
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -154,33 +315,65 @@ impl<'client> Execute<'client> {
         }
     }
 
-    // TODO: pub fn set_limit(&mut self, limit: u64)
+    /// Sets how many more bytes of a binary response may be read, or of
+    /// binary data may be written, before reads or writes return 0.
+    ///
+    /// Once a limit is set on a binary response, [`finish`] skips whatever
+    /// is left of it. This has no effect on other responses.
+    ///
+    /// [`finish`]: #method.finish
+    pub fn set_limit(&mut self, limit: u64) {
+        let mut stream = Stream::None;
+        ::std::mem::swap(&mut stream, &mut self.client.stream);
+        self.client.stream = match (stream, self.code) {
+            (Stream::Raw(s), StatusCode::BinaryResponseFollows) => Stream::Take(s.take(limit)),
+            (Stream::Raw(s), StatusCode::SendBinaryData) => Stream::Give(Give::new(s, limit)),
+            (Stream::Take(mut s), _) => {
+                s.set_limit(limit);
+                Stream::Take(s)
+            },
+            (Stream::Give(s), _) => Stream::Give(Give::new(s.into_inner(), limit)),
+            (stream, _) => stream,
+        };
+    }
 
+    /// Completes the command, skipping any unread response data.
+    ///
+    /// After binary data has been sent for a `SendBinaryData` response, this
+    /// returns the console's final response instead of the initial one. If
+    /// a limit was set and not all of the data was written, this fails and
+    /// the connection is no longer usable.
     pub fn finish(self) -> Result<(StatusCode, String)> {
         let command = self.command;
 
-        match (&mut self.client.stream, self.code) {
+        let result = match (&mut self.client.stream, self.code) {
             (&mut Stream::Dot(ref mut s), StatusCode::MultilineResponseFollows) => {
-                io::copy(s, &mut io::sink())
-                    .map_err(|e| Error::io(e, command))?;
+                io::copy(s, &mut io::sink()).map(|_| ())
             },
             (&mut Stream::Take(ref mut s), StatusCode::BinaryResponseFollows) => {
-                io::copy(s, &mut io::sink())
-                    .map_err(|e| Error::io(e, command))?;
+                io::copy(s, &mut io::sink()).map(|_| ())
             },
+            (&mut Stream::Give(ref s), StatusCode::SendBinaryData) if s.limit() > 0 => {
+                Err(io::Error::new(io::ErrorKind::WriteZero, "binary data was not fully sent"))
+            },
+            (&mut Stream::Give(ref mut s), StatusCode::SendBinaryData) => s.flush(),
             // TODO: We should probably warn the user that the connection state
             // may be invalid if they didn't read/write all of the data.
-            (&mut Stream::Give(_), StatusCode::SendBinaryData) => {},
-            (&mut Stream::Raw(_), StatusCode::BinaryResponseFollows) => {},
-            (&mut Stream::Raw(_), StatusCode::SendBinaryData) => {},
-            (&mut Stream::Raw(_), _) => {},
+            (&mut Stream::Raw(_), StatusCode::BinaryResponseFollows) => Ok(()),
+            (&mut Stream::Raw(ref mut s), StatusCode::SendBinaryData) => s.flush(),
+            (&mut Stream::Raw(_), _) => Ok(()),
             _ => { unreachable!(); },
-        }
+        };
 
         let mut stream = Stream::None;
         ::std::mem::swap(&mut stream, &mut self.client.stream);
         self.client.stream = Stream::Raw(stream.into_inner());
+        result.map_err(|e| Error::io(e, command))?;
 
+        // The console replies again once it has received binary data.
+        if self.code == StatusCode::SendBinaryData {
+            return read_response(self.client.raw(command)?, StatusCode::Ok, command);
+        }
         Ok((self.code, self.message))
     }
 }
@@ -252,37 +445,33 @@ enum DotState {
     Eof,
 }
 
+const DOT_BUFFER_SIZE: usize = 4096;
+
 #[derive(Debug)]
 struct DotReader<R: Read> {
     inner: R,
     state: DotState,
     saved: Option<u8>,
+    buf: Box<[u8]>,
+    pos: usize,
+    cap: usize,
 }
 
 impl<R: Read> DotReader<R> {
     pub fn new(inner: R) -> Self {
         Self {
-            inner: inner,
+            inner,
             state: DotState::BeginLine,
-            saved: None
+            saved: None,
+            buf: vec![0; DOT_BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            cap: 0,
         }
     }
 
     pub fn into_inner(self) -> R { self.inner }
-}
-
-impl<R: BufRead> BufRead for DotReader<R> {
-    fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        self.inner.fill_buf()
-    }
-
-    fn consume(&mut self, amt: usize) {
-        self.inner.consume(amt)
-    }
-}
 
-impl<R: Read> Read for DotReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let mut n = 0;
         while n < buf.len() && self.state != DotState::Eof {
             let mut c = if let Some(c) = self.saved.take() {
@@ -320,6 +509,39 @@ impl<R: Read> Read for DotReader<R> {
     }
 }
 
+impl<R: Read> BufRead for DotReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.cap {
+            let mut buf = ::std::mem::take(&mut self.buf);
+            let result = self.decode(&mut buf);
+            self.buf = buf;
+            self.cap = result?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.cap])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = ::std::cmp::min(self.pos + amt, self.cap);
+    }
+}
+
+impl<R: Read> Read for DotReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.cap && buf.len() >= self.buf.len() {
+            return self.decode(buf);
+        }
+        let n = {
+            let available = self.fill_buf()?;
+            let n = ::std::cmp::min(available.len(), buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
 #[derive(Debug)]
 struct Give<T> {
     inner: T,
@@ -328,10 +550,7 @@ struct Give<T> {
 
 impl<T> Give<T> {
     pub fn new(inner: T, limit: u64) -> Give<T> {
-        Give {
-            inner: inner,
-            limit: limit,
-        }
+        Give { inner, limit }
     }
 
     pub fn limit(&self) -> u64 { self.limit }
@@ -354,9 +573,160 @@ impl<T: Write> Write for Give<T> {
     }
 }
 
+/// Connects a `Client` to a fake console on the loopback interface. The
+/// console sends `script` after the greeting, then closes its side, and returns
+/// everything the client wrote once it disconnects.
+#[cfg(test)]
+pub fn mock_client(script: &[u8]) -> (Client, ::std::thread::JoinHandle<Vec<u8>>) {
+    let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let script = script.to_vec();
+    let handle = ::std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"201- connected\r\n").unwrap();
+        stream.write_all(&script).unwrap();
+        stream.shutdown(::std::net::Shutdown::Write).unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        received
+    });
+    (Client::connect(addr).unwrap(), handle)
+}
+
+#[test]
+fn test_run() {
+    struct Echo(&'static str);
+    impl Command for Echo {
+        type Output = Vec<String>;
+        fn expect(&self) -> &[StatusCode] { &[StatusCode::MultilineResponseFollows] }
+        fn encode(&self) -> String { format!("echo {}", self.0) }
+        fn decode(self, response: &mut Execute) -> Result<Vec<String>> {
+            let mut line = String::new();
+            response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))?;
+            Ok(vec![line])
+        }
+    }
+
+    let (mut client, server) = mock_client(b"202- multiline response follows\r\na\r\nb\r\n.\r\n200- OK\r\n");
+    assert_eq!(client.run(Echo("a")).unwrap(), vec!["a\n".to_owned()]);
+    client.execute(StatusCode::Ok, "echo c").unwrap().finish().unwrap();
+    drop(client);
+    assert_eq!(server.join().unwrap(), b"echo a\r\necho c\r\n");
+}
+
+#[test]
+fn test_run_decode_error() {
+    struct Fail;
+    impl Command for Fail {
+        type Output = ();
+        fn expect(&self) -> &[StatusCode] { &[StatusCode::MultilineResponseFollows] }
+        fn encode(&self) -> String { "fail".to_owned() }
+        fn decode(self, response: &mut Execute) -> Result<()> {
+            Err(Error::bad_response("nope", response.command()))
+        }
+    }
+
+    // The response is skipped, so the decode error is returned.
+    let (mut client, _) = mock_client(b"202- multiline response follows\r\nabc\r\n.\r\n\
+        202- multiline response follows\r\nabc\r\n");
+    match *client.run(Fail).unwrap_err().kind() {
+        ::error::ErrorKind::BadResponse(ref desc) => assert_eq!(desc, "nope"),
+        ref kind => panic!("unexpected error kind: {:?}", kind),
+    }
+    // The response cannot be skipped, so the connection is lost.
+    let err = client.run(Fail).unwrap_err();
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_run_batch() {
+    use error::ErrorCategory;
+
+    struct Poke(u32);
+    impl Command for Poke {
+        type Output = ();
+        fn encode(&self) -> String { format!("poke {}", self.0) }
+        fn decode(self, _: &mut Execute) -> Result<()> { Ok(()) }
+    }
+
+    let (mut client, server) = mock_client(b"200- OK\r\n404- memory not mapped\r\n200- OK\r\nbad\r\n");
+    let results = client.run_batch((0..5).map(Poke));
+    assert!(results[0].is_ok() && results[2].is_ok());
+    assert_eq!(results[1].as_ref().unwrap_err().category(), Some(ErrorCategory::NotMapped));
+    assert!(results[3].as_ref().unwrap_err().is_fatal());
+    assert_eq!(io::Error::from(results.into_iter().nth(4).unwrap().unwrap_err()).kind(),
+               io::ErrorKind::ConnectionAborted);
+    drop(client);
+    assert_eq!(server.join().unwrap(), b"poke 0\r\npoke 1\r\npoke 2\r\npoke 3\r\npoke 4\r\n");
+
+    enum Step { Poke(u32), Put(&'static [u8]) }
+    impl Command for Step {
+        type Output = ();
+        fn expect(&self) -> &[StatusCode] {
+            match *self {
+                Step::Poke(_) => &[StatusCode::Ok],
+                Step::Put(_) => &[StatusCode::SendBinaryData],
+            }
+        }
+        fn encode(&self) -> String {
+            match *self {
+                Step::Poke(n) => format!("poke {}", n),
+                Step::Put(data) => format!("put length=0x{:x}", data.len()),
+            }
+        }
+        fn decode(self, response: &mut Execute) -> Result<()> {
+            if let Step::Put(data) = self {
+                response.set_limit(data.len() as u64);
+                response.write_all(data).map_err(|e| Error::io(e, response.command()))?;
+            }
+            Ok(())
+        }
+    }
+
+    // Commands after a data upload wait for its data to be sent.
+    let (mut client, server) = mock_client(b"200- OK\r\n204- send binary data\r\n200- OK\r\n200- OK\r\n");
+    let results = client.run_batch(vec![Step::Poke(0), Step::Put(b"abc"), Step::Poke(1)]);
+    assert!(results.iter().all(Result::is_ok));
+    drop(client);
+    assert_eq!(server.join().unwrap(), b"poke 0\r\nput length=0x3\r\nabcpoke 1\r\n");
+
+    enum Check { Fail, Poke }
+    impl Command for Check {
+        type Output = ();
+        fn expect(&self) -> &[StatusCode] {
+            match *self {
+                Check::Fail => &[StatusCode::MultilineResponseFollows],
+                Check::Poke => &[StatusCode::Ok],
+            }
+        }
+        fn encode(&self) -> String {
+            match *self {
+                Check::Fail => "fail".to_owned(),
+                Check::Poke => "poke".to_owned(),
+            }
+        }
+        fn decode(self, response: &mut Execute) -> Result<()> {
+            match self {
+                Check::Fail => Err(Error::bad_response("nope", response.command())),
+                Check::Poke => Ok(()),
+            }
+        }
+    }
+
+    // The unfinished response leaves the rest of the batch out of sync.
+    let (mut client, _) = mock_client(b"202- multiline response follows\r\nabc\r\n200- OK\r\n");
+    let results = client.run_batch(vec![Check::Fail, Check::Poke]);
+    assert!(results[0].as_ref().unwrap_err().is_fatal());
+    let err = results.into_iter().nth(1).unwrap().unwrap_err();
+    assert!(err.to_string().contains("may have been run"));
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::ConnectionAborted);
+}
+
 #[test]
 fn test_read_response() {
     assert_eq!(read_response(&mut io::Cursor::new("200- OK\r\n"), StatusCode::Ok, "").unwrap(), (StatusCode::Ok, "OK".to_owned()));
+    assert_eq!(read_response(&mut io::Cursor::new("300- note\r\n200- OK\r\n"), StatusCode::Ok, "").unwrap(), (StatusCode::Ok, "OK".to_owned()));
+    assert!(read_response(&mut io::Cursor::new("300- note\r\n426- operation timed out\r\n"), StatusCode::Ok, "").unwrap_err().is_retryable());
 }
 
 #[test]
@@ -364,4 +734,9 @@ fn test_dot_reader() {
     let mut s = String::new();
     DotReader::new(io::Cursor::new("foo\r\n.\r\n")).read_to_string(&mut s).unwrap();
     assert_eq!(s, "foo\n");
+
+    let mut lines = DotReader::new(io::Cursor::new("a\r\n..b\r\n.\r\nc\r\n")).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "a");
+    assert_eq!(lines.next().unwrap().unwrap(), ".b");
+    assert!(lines.next().is_none());
 }
@@ -0,0 +1,35 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use client::Execute;
+use error::Result;
+use status::StatusCode;
+
+/// A typed XBDM command, run with [`Client::run`].
+///
+/// [`Client::run`]: struct.Client.html#method.run
+pub trait Command {
+    /// The value produced by a successful command.
+    type Output;
+
+    /// The status codes that indicate success.
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::Ok]
+    }
+
+    /// Checks the command's arguments before it is sent, so that a value
+    /// that cannot be encoded safely fails without reaching the console.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Encodes the command line, without the trailing CRLF.
+    fn encode(&self) -> String;
+
+    /// Decodes the response. Any data left unread is discarded afterwards.
+    fn decode(self, response: &mut Execute) -> Result<Self::Output>;
+}
@@ -0,0 +1,25 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::path::PathBuf;
+
+/// Environment variable that overrides the per-user configuration directory.
+pub const CONFIG_DIR_VAR: &str = "XBDM_CONFIG_DIR";
+
+/// Returns the path of the per-user configuration file `name`.
+///
+/// Files live in the directory named by `XBDM_CONFIG_DIR` if it is set, or in
+/// `.xbdm` under the user's home directory otherwise.
+pub fn user_file(name: &str) -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(CONFIG_DIR_VAR) {
+        return Some(PathBuf::from(dir).join(name));
+    }
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".xbdm").join(name))
+}
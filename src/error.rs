@@ -14,7 +14,59 @@ use status::StatusCode;
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// A broad classification of why a command failed, derived from its
+/// `StatusCode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// A file, module, thread or piece of data does not exist.
+    NotFound,
+    /// The memory address is not mapped.
+    NotMapped,
+    /// A file already exists.
+    AlreadyExists,
+    /// A directory is not empty.
+    NotEmpty,
+    /// An argument, such as a filename, is invalid.
+    InvalidInput,
+    /// The console refused access.
+    PermissionDenied,
+    /// The device has no room left.
+    StorageFull,
+    /// The thread must be stopped first.
+    NotStopped,
+    /// The console cannot accept any more connections.
+    Busy,
+    /// The command or operation is not supported by the console or title.
+    Unsupported,
+    /// The console gave up waiting for the operation to complete.
+    TimedOut,
+    /// Any other failure.
+    Other,
+}
+
+impl ErrorCategory {
+    /// The closest matching `io::ErrorKind`.
+    pub fn io_kind(&self) -> io::ErrorKind {
+        match *self {
+            ErrorCategory::NotFound => io::ErrorKind::NotFound,
+            ErrorCategory::NotMapped => io::ErrorKind::NotFound,
+            ErrorCategory::AlreadyExists => io::ErrorKind::AlreadyExists,
+            ErrorCategory::NotEmpty => io::ErrorKind::DirectoryNotEmpty,
+            ErrorCategory::InvalidInput => io::ErrorKind::InvalidInput,
+            ErrorCategory::PermissionDenied => io::ErrorKind::PermissionDenied,
+            ErrorCategory::StorageFull => io::ErrorKind::StorageFull,
+            ErrorCategory::NotStopped => io::ErrorKind::Other,
+            ErrorCategory::Busy => io::ErrorKind::ResourceBusy,
+            ErrorCategory::Unsupported => io::ErrorKind::Unsupported,
+            ErrorCategory::TimedOut => io::ErrorKind::TimedOut,
+            ErrorCategory::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// An I/O error occurred.
     Io(io::Error),
@@ -22,17 +74,31 @@ pub enum ErrorKind {
     BadResponse(String),
     /// A command returned a 4xx status code.
     CommandFailed(StatusCode, String),
+    /// A local reader or writer failed, such as the source of an upload.
+    /// The connection is still usable.
+    Local(io::Error),
 }
 
+/// An error returned by a `Client`.
+///
+/// `Error` is `Send + Sync + 'static`, so it can be moved across threads and
+/// boxed into other error types.
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
     command: String,
+    status_line: Option<String>,
 }
 
 impl Error {
     pub fn new<C: Into<String>>(kind: ErrorKind, command: C) -> Error {
-        Error { kind: kind, command: command.into() }
+        Error { kind, command: command.into(), status_line: None }
+    }
+
+    /// Attaches the response line the error was derived from.
+    pub fn with_status_line<L: Into<String>>(mut self, line: L) -> Error {
+        self.status_line = Some(line.into());
+        self
     }
 
     pub fn io<C: Into<String>>(err: io::Error, command: C) -> Error {
@@ -40,11 +106,15 @@ impl Error {
     }
 
     pub fn io_custom<E, C>(kind: io::ErrorKind, error: E, command: C) -> Error
-        where E: Into<Box<error::Error + Send + Sync>>, C: Into<String>
+        where E: Into<Box<dyn error::Error + Send + Sync>>, C: Into<String>
     {
         Error::new(ErrorKind::Io(io::Error::new(kind, error)), command)
     }
 
+    pub fn local<C: Into<String>>(err: io::Error, command: C) -> Error {
+        Error::new(ErrorKind::Local(err), command)
+    }
+
     pub fn bad_response<D, C>(desc: D, command: C) -> Error
         where D: Into<String>, C: Into<String>
     {
@@ -60,10 +130,42 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind { &self.kind }
     pub fn command(&self) -> &str { &self.command }
 
+    /// The full response line, such as `402- file not found`, if the error
+    /// was caused by one.
+    pub fn status_line(&self) -> Option<&str> {
+        self.status_line.as_ref().map(|s| s.as_ref())
+    }
+
+    /// The category of a failed command, or `None` if the command did not
+    /// return a status code.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match self.kind {
+            ErrorKind::CommandFailed(code, _) => code.category(),
+            _ => None,
+        }
+    }
+
     /// If true, this `Error` requires the `Client` to reconnect.
     pub fn is_fatal(&self) -> bool {
+        matches!(self.kind, ErrorKind::Io(_) | ErrorKind::BadResponse(_))
+    }
+
+    /// If true, the command may succeed if it is tried again later, after
+    /// reconnecting if the error [is fatal].
+    ///
+    /// [is fatal]: #method.is_fatal
+    pub fn is_retryable(&self) -> bool {
         match self.kind {
-            ErrorKind::Io(_) | ErrorKind::BadResponse(_) => true,
+            ErrorKind::Io(ref err) => matches!(err.kind(),
+                io::ErrorKind::TimedOut |
+                io::ErrorKind::WouldBlock |
+                io::ErrorKind::Interrupted |
+                io::ErrorKind::ConnectionReset |
+                io::ErrorKind::ConnectionAborted |
+                io::ErrorKind::UnexpectedEof),
+            ErrorKind::CommandFailed(code, _) => matches!(code,
+                StatusCode::MaxConnectionsExceeded |
+                StatusCode::TimedOut),
             _ => false,
         }
     }
@@ -71,14 +173,15 @@ impl Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let have_command = self.command.len() > 0;
+        let have_command = !self.command.is_empty();
         if have_command {
             write!(f, "command failed: '{}' (", self.command)?;
         }
         match self.kind {
             ErrorKind::Io(ref err) => write!(f, "I/O error: {}", err)?,
             ErrorKind::BadResponse(ref desc) => write!(f, "bad response: {}", desc)?,
-            ErrorKind::CommandFailed(code, ref msg) => write!(f, "{}- {}", code, msg)?,
+            ErrorKind::CommandFailed(code, ref msg) => write!(f, "{}- {}", code.to_u16(), msg)?,
+            ErrorKind::Local(ref err) => write!(f, "local I/O error: {}", err)?,
         }
         if have_command {
             write!(f, ")")?;
@@ -88,18 +191,56 @@ impl fmt::Display for Error {
 }
 
 impl error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self.kind {
-            ErrorKind::Io(ref err) => err.description(),
-            ErrorKind::BadResponse(ref desc) => desc.as_ref(),
-            ErrorKind::CommandFailed(_, ref msg) => msg.as_ref(),
+            ErrorKind::Io(ref err) | ErrorKind::Local(ref err) => Some(err),
+            _ => None,
         }
     }
+}
 
-    fn cause(&self) -> Option<&::std::error::Error> {
-        match self.kind {
-            ErrorKind::Io(ref err) => Some(err),
-            _ => None,
-        }
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        let kind = match err.kind {
+            ErrorKind::Io(ref e) | ErrorKind::Local(ref e) => e.kind(),
+            ErrorKind::BadResponse(_) => io::ErrorKind::InvalidData,
+            ErrorKind::CommandFailed(..) => err.category()
+                .map_or(io::ErrorKind::Other, |c| c.io_kind()),
+        };
+        io::Error::new(kind, err)
     }
 }
+
+#[test]
+fn test_into_io_error() {
+    let err = Error::command_failed(StatusCode::FileExists, "file already exists", "mkdir");
+    assert_eq!(err.category(), Some(ErrorCategory::AlreadyExists));
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::AlreadyExists);
+
+    let err = Error::bad_response("too short", "getmem");
+    assert_eq!(err.category(), None);
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_error_traits() {
+    fn assert_traits<T: error::Error + Send + Sync + 'static>() {}
+    assert_traits::<Error>();
+
+    let err = Error::command_failed(StatusCode::MaxConnectionsExceeded, "max number of connections exceeded", "")
+        .with_status_line("401- max number of connections exceeded");
+    assert!(err.is_retryable());
+    assert!(error::Error::source(&err).is_none());
+    assert!(Error::command_failed(StatusCode::TimedOut, "operation timed out", "").is_retryable());
+    assert_eq!(err.status_line(), Some("401- max number of connections exceeded"));
+
+    let err = Error::io_custom(io::ErrorKind::TimedOut, "timed out", "getmem");
+    assert!(err.is_retryable());
+    assert!(error::Error::source(&err).is_some());
+
+    let err = Error::local(io::Error::new(io::ErrorKind::UnexpectedEof, "reader ended early"),
+                           "sendfile");
+    assert!(!err.is_fatal());
+    assert!(!err.is_retryable());
+    assert!(error::Error::source(&err).is_some());
+}
@@ -0,0 +1,495 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::io;
+use std::io::prelude::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use client::{Client, Execute};
+use command::Command;
+use error::{Error, Result};
+use params::Params;
+use status::StatusCode;
+
+/// Seconds between the FILETIME epoch (1601-01-01) and the Unix epoch.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+/// Converts a Windows FILETIME, in 100 nanosecond intervals since 1601, to a
+/// `SystemTime`.
+pub(crate) fn filetime_to_system_time(filetime: u64) -> SystemTime {
+    let since_1601 = Duration::new(filetime / 10_000_000, (filetime % 10_000_000) as u32 * 100);
+    let offset = Duration::from_secs(FILETIME_UNIX_OFFSET);
+    if since_1601 >= offset {
+        UNIX_EPOCH + (since_1601 - offset)
+    } else {
+        UNIX_EPOCH - (offset - since_1601)
+    }
+}
+
+/// Converts a `SystemTime` to a Windows FILETIME, saturating at the bounds of
+/// the FILETIME range.
+pub(crate) fn system_time_to_filetime(time: SystemTime) -> u64 {
+    let offset = Duration::from_secs(FILETIME_UNIX_OFFSET);
+    let since_1601 = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => offset + d,
+        Err(e) => offset.checked_sub(e.duration()).unwrap_or_default(),
+    };
+    let intervals = since_1601.as_secs() as u128 * 10_000_000 +
+        since_1601.subsec_nanos() as u128 / 100;
+    cmp::min(intervals, u64::MAX as u128) as u64
+}
+
+/// Fails with a local `InvalidInput` error if `path` cannot be quoted in
+/// `command`: a `"` would end the name early and let the rest be read as
+/// parameters, and a line break would start another command.
+pub(crate) fn check_quoted(path: &str, command: &str) -> Result<()> {
+    match path.chars().find(|&c| c == '"' || c == '\r' || c == '\n' || c == '\0') {
+        Some(c) => Err(Error::local(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("path contains {:?}: {:?}", c, path)), command)),
+        None => Ok(()),
+    }
+}
+
+/// A file or directory listed by `dirlist`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DirEntry {
+    pub name: String,
+    pub size: u64,
+    pub create_time: SystemTime,
+    pub change_time: SystemTime,
+    pub is_directory: bool,
+    pub is_readonly: bool,
+    pub is_hidden: bool,
+}
+
+impl DirEntry {
+    fn parse(params: &Params) -> Option<DirEntry> {
+        let attributes = FileAttributes::parse(params)?;
+        Some(DirEntry {
+            name: params.get("name")?.to_owned(),
+            size: attributes.size,
+            create_time: attributes.create_time,
+            change_time: attributes.change_time,
+            is_directory: attributes.is_directory,
+            is_readonly: attributes.is_readonly,
+            is_hidden: attributes.is_hidden,
+        })
+    }
+}
+
+/// The attributes of a file or directory returned by `getfileattributes`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FileAttributes {
+    pub size: u64,
+    pub create_time: SystemTime,
+    pub change_time: SystemTime,
+    pub is_directory: bool,
+    pub is_readonly: bool,
+    pub is_hidden: bool,
+}
+
+impl FileAttributes {
+    fn parse(params: &Params) -> Option<FileAttributes> {
+        Some(FileAttributes {
+            size: params.get_u64("size")?,
+            create_time: filetime_to_system_time(params.get_u64("create")?),
+            change_time: filetime_to_system_time(params.get_u64("change")?),
+            is_directory: params.has("directory"),
+            is_readonly: params.has("readonly"),
+            is_hidden: params.has("hidden"),
+        })
+    }
+}
+
+/// The capacity of a drive returned by `drivefreespace`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DriveSpace {
+    /// The size of the drive in bytes.
+    pub total: u64,
+    /// The number of free bytes on the drive.
+    pub free: u64,
+    /// The number of free bytes available to the debugger, which may be
+    /// less than `free`.
+    pub available: u64,
+}
+
+/// Lists the contents of a directory with `dirlist`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DirList<'a> {
+    pub path: &'a str,
+}
+
+impl<'a> Command for DirList<'a> {
+    type Output = Vec<DirEntry>;
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.path, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        format!("dirlist name=\"{}\"", self.path)
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))? == 0 {
+                break;
+            }
+            match DirEntry::parse(&Params::parse(&line)) {
+                Some(entry) => entries.push(entry),
+                None => return Err(Error::bad_response(
+                    format!("invalid directory entry: {}", line.trim_end()), response.command())),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Deletes a file, or an empty directory, with `delete`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Delete<'a> {
+    pub path: &'a str,
+    pub is_directory: bool,
+}
+
+impl<'a> Command for Delete<'a> {
+    type Output = ();
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.path, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        if self.is_directory {
+            format!("delete name=\"{}\" dir", self.path)
+        } else {
+            format!("delete name=\"{}\"", self.path)
+        }
+    }
+
+    fn decode(self, _: &mut Execute) -> Result<()> { Ok(()) }
+}
+
+/// Creates a directory with `mkdir`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MakeDir<'a> {
+    pub path: &'a str,
+}
+
+impl<'a> Command for MakeDir<'a> {
+    type Output = ();
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.path, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        format!("mkdir name=\"{}\"", self.path)
+    }
+
+    fn decode(self, _: &mut Execute) -> Result<()> { Ok(()) }
+}
+
+/// Renames or moves a file or directory with `rename`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rename<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+}
+
+impl<'a> Command for Rename<'a> {
+    type Output = ();
+
+    fn validate(&self) -> Result<()> {
+        let command = self.encode();
+        check_quoted(self.from, &command)?;
+        check_quoted(self.to, &command)
+    }
+
+    fn encode(&self) -> String {
+        format!("rename name=\"{}\" newname=\"{}\"", self.from, self.to)
+    }
+
+    fn decode(self, _: &mut Execute) -> Result<()> { Ok(()) }
+}
+
+/// Reads the attributes of a file or directory with `getfileattributes`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GetFileAttributes<'a> {
+    pub path: &'a str,
+}
+
+impl<'a> Command for GetFileAttributes<'a> {
+    type Output = FileAttributes;
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.path, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        format!("getfileattributes name=\"{}\"", self.path)
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<FileAttributes> {
+        let mut line = String::new();
+        response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))?;
+        FileAttributes::parse(&Params::parse(&line)).ok_or_else(|| Error::bad_response(
+            format!("invalid file attributes: {}", line.trim_end()), response.command()))
+    }
+}
+
+/// Changes the attributes of a file or directory with `setfileattributes`.
+/// Attributes left as `None` are not changed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SetFileAttributes<'a> {
+    pub path: &'a str,
+    pub readonly: Option<bool>,
+    pub hidden: Option<bool>,
+    pub create_time: Option<SystemTime>,
+    pub change_time: Option<SystemTime>,
+}
+
+impl<'a> SetFileAttributes<'a> {
+    /// Creates a command that changes nothing.
+    pub fn new(path: &'a str) -> SetFileAttributes<'a> {
+        SetFileAttributes {
+            path,
+            readonly: None,
+            hidden: None,
+            create_time: None,
+            change_time: None,
+        }
+    }
+}
+
+impl<'a> Command for SetFileAttributes<'a> {
+    type Output = ();
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.path, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        let mut command = format!("setfileattributes name=\"{}\"", self.path);
+        if let Some(readonly) = self.readonly {
+            command.push_str(&format!(" readonly={}", readonly as u8));
+        }
+        if let Some(hidden) = self.hidden {
+            command.push_str(&format!(" hidden={}", hidden as u8));
+        }
+        for &(key, time) in &[("create", self.create_time), ("change", self.change_time)] {
+            if let Some(time) = time {
+                let filetime = system_time_to_filetime(time);
+                command.push_str(&format!(" {}hi=0x{:08x} {}lo=0x{:08x}",
+                                          key, filetime >> 32, key, filetime as u32));
+            }
+        }
+        command
+    }
+
+    fn decode(self, _: &mut Execute) -> Result<()> { Ok(()) }
+}
+
+/// Lists the names of the console's drives with `drivelist`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DriveList;
+
+impl Command for DriveList {
+    type Output = Vec<String>;
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn encode(&self) -> String {
+        "drivelist".to_owned()
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<Vec<String>> {
+        let mut drives = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))? == 0 {
+                break;
+            }
+            match Params::parse(&line).get("drivename") {
+                Some(name) => drives.push(name.to_owned()),
+                None => return Err(Error::bad_response(
+                    format!("invalid drive: {}", line.trim_end()), response.command())),
+            }
+        }
+        Ok(drives)
+    }
+}
+
+/// Reads the capacity of a drive with `drivefreespace`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DriveFreeSpace<'a> {
+    /// The drive name, such as `E` or `DEVKIT`.
+    pub drive: &'a str,
+}
+
+impl<'a> Command for DriveFreeSpace<'a> {
+    type Output = DriveSpace;
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn validate(&self) -> Result<()> {
+        check_quoted(self.drive, &self.encode())
+    }
+
+    fn encode(&self) -> String {
+        format!("drivefreespace name=\"{}:\\\"", self.drive)
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<DriveSpace> {
+        let mut line = String::new();
+        response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))?;
+        let params = Params::parse(&line);
+        match (params.get_u64("totalbytes"), params.get_u64("totalfreebytes"),
+               params.get_u64("freetocaller")) {
+            (Some(total), Some(free), Some(available)) => Ok(DriveSpace { total, free, available }),
+            _ => Err(Error::bad_response(
+                format!("invalid free space: {}", line.trim_end()), response.command())),
+        }
+    }
+}
+
+impl Client {
+    /// Lists the files and directories in `path`, such as `E:\Games`.
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<DirEntry>> {
+        self.run(DirList { path })
+    }
+
+    /// Deletes the file at `path`.
+    pub fn remove_file(&mut self, path: &str) -> Result<()> {
+        self.run(Delete { path, is_directory: false })
+    }
+
+    /// Deletes the directory at `path`, which must be empty.
+    pub fn remove_dir(&mut self, path: &str) -> Result<()> {
+        self.run(Delete { path, is_directory: true })
+    }
+
+    /// Creates a directory at `path`. Its parent must already exist.
+    pub fn create_dir(&mut self, path: &str) -> Result<()> {
+        self.run(MakeDir { path })
+    }
+
+    /// Renames `from` to `to`, which must not already exist.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        self.run(Rename { from, to })
+    }
+
+    pub fn file_attributes(&mut self, path: &str) -> Result<FileAttributes> {
+        self.run(GetFileAttributes { path })
+    }
+
+    /// Changes the attributes set in `attributes`.
+    pub fn set_file_attributes(&mut self, attributes: SetFileAttributes) -> Result<()> {
+        self.run(attributes)
+    }
+
+    /// Lists the names of the console's drives, such as `E` and `DEVKIT`.
+    pub fn drives(&mut self) -> Result<Vec<String>> {
+        self.run(DriveList)
+    }
+
+    /// Returns the total, free and available bytes on `drive`.
+    pub fn drive_space(&mut self, drive: &str) -> Result<DriveSpace> {
+        self.run(DriveFreeSpace { drive })
+    }
+}
+
+#[test]
+fn test_read_dir() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"202- multiline response follows\r\n\
+        name=\"default.xex\" sizehi=0x1 sizelo=0x10 createhi=0x019db1de createlo=0xd53e8000 \
+        changehi=0x019db1de changelo=0xd53e8000 readonly\r\n\
+        name=\"Saves\" sizehi=0x0 sizelo=0x0 createhi=0x0 createlo=0x0 \
+        changehi=0x0 changelo=0x0 directory hidden\r\n.\r\n\
+        402- file not found\r\n");
+    let entries = client.read_dir(r"E:\My Game").unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, "default.xex");
+    assert_eq!(entries[0].size, 0x1_0000_0010);
+    assert_eq!(entries[0].create_time, UNIX_EPOCH);
+    assert!(entries[0].is_readonly && !entries[0].is_directory);
+    assert!(entries[1].is_directory && entries[1].is_hidden);
+    assert_eq!(entries[1].change_time,
+               UNIX_EPOCH - Duration::from_secs(FILETIME_UNIX_OFFSET));
+
+    // A quote would let the rest of the path be read as parameters.
+    let err = client.remove_file("E:\\a\" dir").unwrap_err();
+    assert!(!err.is_fatal());
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidInput);
+
+    let err = client.read_dir(r"E:\Missing").unwrap_err();
+    assert_eq!(err.category(), Some(::error::ErrorCategory::NotFound));
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert!(sent.contains("dirlist name=\"E:\\My Game\"\r\n"));
+    assert!(!sent.contains("delete"));
+}
+
+#[test]
+fn test_file_commands() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"\
+        200- OK\r\n200- OK\r\n200- OK\r\n200- OK\r\n200- OK\r\n\
+        202- multiline response follows\r\nsizehi=0x0 sizelo=0x20 createhi=0x019db1de \
+        createlo=0xd53e8000 changehi=0x019db1de changelo=0xd53e8000 hidden\r\n.\r\n\
+        202- multiline response follows\r\ndrivename=\"E\"\r\ndrivename=\"DEVKIT\"\r\n.\r\n\
+        202- multiline response follows\r\nfreetocallerhi=0x0 freetocallerlo=0x100 \
+        totalbyteshi=0x1 totalbyteslo=0x0 totalfreebyteshi=0x0 totalfreebyteslo=0x200\r\n.\r\n");
+    client.remove_file(r"E:\a").unwrap();
+    client.remove_dir(r"E:\b").unwrap();
+    client.create_dir(r"E:\c").unwrap();
+    client.rename(r"E:\c", r"E:\d").unwrap();
+    client.set_file_attributes(SetFileAttributes {
+        readonly: Some(true),
+        change_time: Some(UNIX_EPOCH),
+        ..SetFileAttributes::new(r"E:\d")
+    }).unwrap();
+    let attributes = client.file_attributes(r"E:\d").unwrap();
+    assert_eq!(attributes.size, 0x20);
+    assert_eq!(attributes.change_time, UNIX_EPOCH);
+    assert!(attributes.is_hidden && !attributes.is_directory);
+    assert_eq!(client.drives().unwrap(), vec!["E", "DEVKIT"]);
+    assert_eq!(client.drive_space("E").unwrap(),
+               DriveSpace { total: 0x1_0000_0000, free: 0x200, available: 0x100 });
+
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert_eq!(sent, "\
+        delete name=\"E:\\a\"\r\n\
+        delete name=\"E:\\b\" dir\r\n\
+        mkdir name=\"E:\\c\"\r\n\
+        rename name=\"E:\\c\" newname=\"E:\\d\"\r\n\
+        setfileattributes name=\"E:\\d\" readonly=1 changehi=0x019db1de changelo=0xd53e8000\r\n\
+        getfileattributes name=\"E:\\d\"\r\n\
+        drivelist\r\n\
+        drivefreespace name=\"E:\\\"\r\n");
+}
@@ -6,16 +6,51 @@
 // copied, modified, or distributed except according to those terms.
 
 extern crate bufstream;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 mod client;
+mod command;
+mod config;
 mod error;
+mod fs;
+mod memory;
+mod params;
+mod patch;
+mod path;
+mod region;
+mod relay;
+mod remote_memory;
+mod resolver;
+mod scan;
+mod snapshot;
 mod status;
+mod transfer;
+mod values;
+mod watch;
 mod xbox;
 
 pub use client::{Client, Execute};
-pub use error::{Error, ErrorKind, Result};
-pub use status::StatusCode;
-pub use xbox::{Discover, Xbox, discover, resolve, resolve_ip, resolve_name};
+pub use command::Command;
+pub use error::{Error, ErrorCategory, ErrorKind, Result};
+pub use fs::{Delete, DirEntry, DirList, DriveFreeSpace, DriveList, DriveSpace, FileAttributes,
+             GetFileAttributes, MakeDir, Rename, SetFileAttributes};
+pub use memory::{GetMem, GetMemEx, MAX_READ_CHUNK, MAX_READ_CHUNK_EX, MAX_WRITE_CHUNK, MemoryBlock,
+                 SetMem};
+pub use params::Params;
+pub use patch::{Patch, PatchSet};
+pub use path::{InvalidPathError, XboxPath, XboxPathBuf};
+pub use region::{MemoryRegion, Protection, WalkMem};
+pub use relay::Relay;
+pub use remote_memory::{DEFAULT_PAGE_SIZE, RemoteMemory};
+pub use resolver::{CONSOLE_VAR, HOSTS_VAR, Resolver, default_console, resolve};
+pub use scan::{DEFAULT_SCAN_CHUNK, ParsePatternError, Pattern, Scanner};
+pub use snapshot::{Predicate, Snapshot, ValueFinder};
+pub use status::{ParseStatusCodeError, StatusCode};
+pub use transfer::{DEFAULT_RESUME_CHUNK, Download, Progress, ResumableUpload, TEMP_SUFFIX, Upload};
+pub use values::{MemoryValue, Value, ValueType};
+pub use watch::{WatchEvent, WatchHandle, Watcher};
+pub use xbox::{Discover, Endian, Platform, Xbox, discover, resolve_ip, resolve_name};
 
 /// TCP/UDP port number used by the Xbox 360 for XBDM.
 pub const PORT_360: u16 = 730;
@@ -0,0 +1,401 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::io::prelude::*;
+
+use client::{Client, Execute};
+use command::Command;
+use error::{Error, ErrorCategory, Result};
+use status::StatusCode;
+use xbox::Platform;
+
+/// Largest number of bytes requested by a single `getmem` command.
+pub const MAX_READ_CHUNK: usize = 0x1000;
+
+/// Largest number of bytes requested by a single `getmemex` command.
+pub const MAX_READ_CHUNK_EX: usize = 0x10000;
+
+/// Flag set in a `getmemex` block header when the block is not mapped.
+const BLOCK_UNMAPPED: u16 = 0x8000;
+
+/// Largest number of bytes sent by a single `setmem` command, chosen to keep
+/// the command line well under the console's 512 byte limit.
+pub const MAX_WRITE_CHUNK: usize = 128;
+
+/// A range of console memory along with which of its bytes are mapped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryBlock {
+    addr: u32,
+    data: Vec<u8>,
+    mapped: Vec<bool>,
+}
+
+impl MemoryBlock {
+    /// Creates a block from bytes and their mask.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` and `mapped` differ in length.
+    pub fn new(addr: u32, data: Vec<u8>, mapped: Vec<bool>) -> MemoryBlock {
+        assert_eq!(data.len(), mapped.len(), "data and mask lengths differ");
+        MemoryBlock { addr, data, mapped }
+    }
+
+    /// The address of the first byte.
+    pub fn addr(&self) -> u32 { self.addr }
+
+    /// The bytes read. Unmapped bytes are zero.
+    pub fn data(&self) -> &[u8] { &self.data }
+
+    /// Whether each byte in [`data`] was mapped.
+    ///
+    /// [`data`]: #method.data
+    pub fn mapped(&self) -> &[bool] { &self.mapped }
+
+    /// Whether every byte was mapped.
+    pub fn is_fully_mapped(&self) -> bool {
+        self.mapped.iter().all(|&m| m)
+    }
+
+    pub fn into_data(self) -> Vec<u8> { self.data }
+}
+
+/// Reads up to `MAX_READ_CHUNK` bytes with `getmem`. Unmapped bytes, which
+/// the console reports as `??`, are returned as zero with a `false` mask.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GetMem {
+    pub addr: u32,
+    pub len: usize,
+}
+
+impl Command for GetMem {
+    type Output = (Vec<u8>, Vec<bool>);
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn encode(&self) -> String {
+        format!("getmem addr=0x{:08x} length=0x{:x}", self.addr, self.len)
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<(Vec<u8>, Vec<bool>)> {
+        let mut data = Vec::with_capacity(self.len);
+        let mut mapped = Vec::with_capacity(self.len);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))? == 0 {
+                break;
+            }
+            parse_hex_line(line.trim_end(), &mut data, &mut mapped)
+                .map_err(|desc| Error::bad_response(desc, response.command()))?;
+        }
+        if data.len() != self.len {
+            return Err(Error::bad_response(
+                format!("expected {} bytes, got {}", self.len, data.len()),
+                response.command()));
+        }
+        Ok((data, mapped))
+    }
+}
+
+/// Reads memory with `getmemex`, which is supported by Xbox 360 consoles and
+/// returns raw bytes rather than hex text.
+///
+/// The binary response is a series of blocks, each preceded by a two byte
+/// little-endian header holding the block's length. If the header's high bit
+/// is set, the block is unmapped and no data follows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GetMemEx {
+    pub addr: u32,
+    pub len: usize,
+}
+
+impl Command for GetMemEx {
+    type Output = (Vec<u8>, Vec<bool>);
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::BinaryResponseFollows]
+    }
+
+    fn encode(&self) -> String {
+        format!("getmemex addr=0x{:08x} length=0x{:x}", self.addr, self.len)
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<(Vec<u8>, Vec<bool>)> {
+        let mut data = vec![0; self.len];
+        let mut mapped = vec![false; self.len];
+        let mut pos = 0;
+        while pos < self.len {
+            let mut header = [0; 2];
+            response.read_exact(&mut header).map_err(|e| Error::io(e, response.command()))?;
+            let header = u16::from_le_bytes(header);
+            let n = (header & !BLOCK_UNMAPPED) as usize;
+            if n == 0 || n > self.len - pos {
+                return Err(Error::bad_response(
+                    format!("invalid block length {}", n), response.command()));
+            }
+            if header & BLOCK_UNMAPPED == 0 {
+                response.read_exact(&mut data[pos..pos + n])
+                    .map_err(|e| Error::io(e, response.command()))?;
+                for m in &mut mapped[pos..pos + n] {
+                    *m = true;
+                }
+            }
+            pos += n;
+        }
+        Ok((data, mapped))
+    }
+}
+
+pub(crate) fn parse_hex_line(line: &str, data: &mut Vec<u8>, mapped: &mut Vec<bool>)
+    -> ::std::result::Result<(), &'static str>
+{
+    let line = line.as_bytes();
+    if !line.len().is_multiple_of(2) {
+        return Err("odd number of hex digits");
+    }
+    for pair in line.chunks(2) {
+        if pair == b"??" {
+            data.push(0);
+            mapped.push(false);
+            continue;
+        }
+        let hi = (pair[0] as char).to_digit(16);
+        let lo = (pair[1] as char).to_digit(16);
+        match (hi, lo) {
+            (Some(hi), Some(lo)) => {
+                data.push((hi << 4 | lo) as u8);
+                mapped.push(true);
+            },
+            _ => return Err("invalid hex digit"),
+        }
+    }
+    Ok(())
+}
+
+/// Writes up to `MAX_WRITE_CHUNK` bytes with `setmem`, returning the number of
+/// bytes the console reports were set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SetMem<'a> {
+    pub addr: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Command for SetMem<'a> {
+    type Output = usize;
+
+    fn encode(&self) -> String {
+        let mut command = format!("setmem addr=0x{:08x} data=", self.addr);
+        for b in self.data {
+            command.push_str(&format!("{:02x}", b));
+        }
+        command
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<usize> {
+        // The console replies "set N bytes", where N stops short at the
+        // first unmapped byte.
+        let n = {
+            let mut words = response.message().split_whitespace();
+            match (words.next(), words.next().map(str::parse), words.next()) {
+                (Some("set"), Some(Ok(n)), Some("bytes")) => n,
+                _ => return Err(Error::bad_response(
+                    format!("unexpected message: {}", response.message()),
+                    response.command())),
+            }
+        };
+        if n > self.data.len() {
+            return Err(Error::bad_response(
+                format!("set {} bytes, but only {} were sent", n, self.data.len()),
+                response.command()));
+        }
+        Ok(n)
+    }
+}
+
+impl Client {
+    /// Reads `len` bytes of memory starting at `addr`.
+    ///
+    /// Fails with `MemoryNotMapped` if any byte in the range is unmapped. Use
+    /// [`read_memory_block`] to read around unmapped bytes instead.
+    ///
+    /// [`read_memory_block`]: #method.read_memory_block
+    pub fn read_memory(&mut self, addr: u32, len: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len);
+        for (chunk_addr, chunk_len) in self.read_chunks(addr, len) {
+            let (chunk, mapped) = self.read_chunk(chunk_addr, chunk_len)?;
+            if mapped.iter().any(|&m| !m) {
+                let cmd = GetMem { addr: chunk_addr, len: chunk_len };
+                let message = StatusCode::MemoryNotMapped.default_message().unwrap_or("");
+                return Err(Error::command_failed(
+                    StatusCode::MemoryNotMapped, message, cmd.encode()));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    /// Reads `len` bytes of memory starting at `addr`, recording which bytes
+    /// are mapped rather than failing. Chunks the console rejects as not
+    /// mapped are treated as entirely unmapped.
+    pub fn read_memory_block(&mut self, addr: u32, len: usize) -> Result<MemoryBlock> {
+        let mut data = Vec::with_capacity(len);
+        let mut mapped = Vec::with_capacity(len);
+        for (chunk_addr, chunk_len) in self.read_chunks(addr, len) {
+            match self.read_chunk(chunk_addr, chunk_len) {
+                Ok((d, m)) => {
+                    data.extend_from_slice(&d);
+                    mapped.extend_from_slice(&m);
+                },
+                Err(ref e) if e.category() == Some(ErrorCategory::NotMapped) => {
+                    data.resize(data.len() + chunk_len, 0);
+                    mapped.resize(mapped.len() + chunk_len, false);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(MemoryBlock { addr, data, mapped })
+    }
+
+    /// Writes `data` to memory starting at `addr` and returns how many bytes
+    /// were written.
+    ///
+    /// As with `io::Write::write`, a short count means writing stopped at an
+    /// unmapped byte. Fails with `MemoryNotMapped` if no bytes were written.
+    pub fn write_memory(&mut self, addr: u32, data: &[u8]) -> Result<usize> {
+        let mut written = 0;
+        for chunk in data.chunks(MAX_WRITE_CHUNK) {
+            let cmd = SetMem { addr: addr.wrapping_add(written as u32), data: chunk };
+            let n = match self.run(cmd) {
+                Ok(n) => n,
+                Err(ref e) if written > 0 && e.category() == Some(ErrorCategory::NotMapped) => 0,
+                Err(e) => return Err(e),
+            };
+            written += n;
+            if n < chunk.len() {
+                break;
+            }
+        }
+        if written == 0 && !data.is_empty() {
+            let cmd = SetMem { addr, data: &data[..cmp::min(data.len(), MAX_WRITE_CHUNK)] };
+            let message = StatusCode::MemoryNotMapped.default_message().unwrap_or("");
+            return Err(Error::command_failed(StatusCode::MemoryNotMapped, message, cmd.encode()));
+        }
+        Ok(written)
+    }
+
+    /// Writes all of `data`, failing with `MemoryNotMapped` on a short write.
+    pub fn write_memory_all(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        let n = self.write_memory(addr, data)?;
+        if n < data.len() {
+            let message = StatusCode::MemoryNotMapped.default_message().unwrap_or("");
+            return Err(Error::command_failed(StatusCode::MemoryNotMapped, message,
+                format!("setmem addr=0x{:08x}", addr.wrapping_add(n as u32))));
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    fn read_chunks(&self, addr: u32, len: usize) -> impl Iterator<Item=(u32, usize)> {
+        let size = match self.platform() {
+            Some(Platform::Xbox360) => MAX_READ_CHUNK_EX,
+            _ => MAX_READ_CHUNK,
+        };
+        chunks(addr, len, size)
+    }
+
+    /// Reads with `getmemex` on an Xbox 360, falling back to `getmem` for
+    /// the rest of the connection if the console does not know the command.
+    fn read_chunk(&mut self, addr: u32, len: usize) -> Result<(Vec<u8>, Vec<bool>)> {
+        if self.platform() == Some(Platform::Xbox360) && !self.getmemex_unsupported {
+            match self.run(GetMemEx { addr, len }) {
+                Err(ref e) if e.category() == Some(ErrorCategory::Unsupported) => {
+                    self.getmemex_unsupported = true;
+                },
+                result => return result,
+            }
+        }
+        let mut data = Vec::with_capacity(len);
+        let mut mapped = Vec::with_capacity(len);
+        for (addr, len) in chunks(addr, len, MAX_READ_CHUNK) {
+            let (d, m) = self.run(GetMem { addr, len })?;
+            data.extend_from_slice(&d);
+            mapped.extend_from_slice(&m);
+        }
+        Ok((data, mapped))
+    }
+}
+
+fn chunks(addr: u32, len: usize, size: usize) -> impl Iterator<Item=(u32, usize)> {
+    (0..len).step_by(size).map(move |offset| {
+        (addr.wrapping_add(offset as u32), cmp::min(size, len - offset))
+    })
+}
+
+#[test]
+fn test_read_memory() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"202- multiline response follows\r\n\
+        0102??04\r\n05\r\n.\r\n\
+        404- memory not mapped\r\n");
+    let block = client.read_memory_block(0x8000_0000, 5).unwrap();
+    assert_eq!(block.data(), &[1, 2, 0, 4, 5]);
+    assert_eq!(block.mapped(), &[true, true, false, true, true]);
+    let err = client.read_memory(0x1000, 2).unwrap_err();
+    assert_eq!(err.category(), Some(ErrorCategory::NotMapped));
+    drop(client);
+    assert_eq!(server.join().unwrap(),
+               &b"getmem addr=0x80000000 length=0x5\r\ngetmem addr=0x00001000 length=0x2\r\n"[..]);
+
+    assert_eq!(chunks(0, MAX_READ_CHUNK + 1, MAX_READ_CHUNK).collect::<Vec<_>>(),
+               vec![(0, MAX_READ_CHUNK), (MAX_READ_CHUNK as u32, 1)]);
+}
+
+#[test]
+fn test_read_memory_ex() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"203- binary response follows\r\n\
+        \x02\x00\xaa\xbb\x03\x80\x01\x00\xcc\
+        407- unknown command\r\n\
+        202- multiline response follows\r\n0102\r\n.\r\n\
+        202- multiline response follows\r\n03\r\n.\r\n");
+    client.set_platform(Some(Platform::Xbox360));
+    let block = client.read_memory_block(0x8000_0000, 6).unwrap();
+    assert_eq!(block.data(), &[0xaa, 0xbb, 0, 0, 0, 0xcc]);
+    assert_eq!(block.mapped(), &[true, true, false, false, false, true]);
+    assert_eq!(client.read_memory(0x1000, 2).unwrap(), vec![1, 2]);
+    // The fallback is remembered.
+    assert_eq!(client.read_memory(0x2000, 1).unwrap(), vec![3]);
+    drop(client);
+    assert_eq!(server.join().unwrap(), &b"getmemex addr=0x80000000 length=0x6\r\n\
+        getmemex addr=0x00001000 length=0x2\r\ngetmem addr=0x00001000 length=0x2\r\n\
+        getmem addr=0x00002000 length=0x1\r\n"[..]);
+}
+
+#[test]
+fn test_write_memory() {
+    use client::mock_client;
+
+    let data = vec![0xab; MAX_WRITE_CHUNK + 4];
+    let (mut client, server) = mock_client(b"200- set 128 bytes\r\n200- set 2 bytes\r\n200- set 0 bytes\r\n");
+    assert_eq!(client.write_memory(0x8000_0000, &data).unwrap(), MAX_WRITE_CHUNK + 2);
+    let err = client.write_memory(0x1000, &[1, 2]).unwrap_err();
+    assert_eq!(err.category(), Some(ErrorCategory::NotMapped));
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    let lines = sent.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("setmem addr=0x80000000 data=abab"));
+    assert_eq!(lines[1], "setmem addr=0x80000080 data=abababab");
+    assert_eq!(lines[2], "setmem addr=0x00001000 data=0102");
+}
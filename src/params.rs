@@ -0,0 +1,92 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// The `key=value` parameters of a response line, such as
+/// `name="default.xex" sizehi=0x0 sizelo=0x1000 directory`.
+///
+/// Keys are matched case-insensitively. Keys without a value, like
+/// `directory`, are flags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Params<'a> {
+    pairs: Vec<(&'a str, Option<&'a str>)>,
+}
+
+impl<'a> Params<'a> {
+    /// Parses `line`. Values may be quoted to include spaces.
+    pub fn parse(line: &'a str) -> Params<'a> {
+        let mut pairs = Vec::new();
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let key_end = rest.find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = &rest[key_end..];
+            let value = if let Some(after) = rest.strip_prefix('=') {
+                let (value, remainder) = if let Some(quoted) = after.strip_prefix('"') {
+                    match quoted.find('"') {
+                        Some(n) => (&quoted[..n], &quoted[n + 1..]),
+                        None => (quoted, ""),
+                    }
+                } else {
+                    let n = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..n], &after[n..])
+                };
+                rest = remainder;
+                Some(value)
+            } else {
+                None
+            };
+            pairs.push((key, value));
+            rest = rest.trim_start();
+        }
+        Params { pairs }
+    }
+
+    /// The value of `key`, or `None` if it is missing or a flag.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.pairs.iter()
+            .find(|&&(k, _)| k.eq_ignore_ascii_case(key))
+            .and_then(|&(_, v)| v)
+    }
+
+    /// Whether `key` is present, with or without a value.
+    pub fn has(&self, key: &str) -> bool {
+        self.pairs.iter().any(|&(k, _)| k.eq_ignore_ascii_case(key))
+    }
+
+    /// The value of `key` as a number, which is hexadecimal if prefixed with
+    /// `0x` and decimal otherwise.
+    pub fn get_u32(&self, key: &str) -> Option<u32> {
+        let value = self.get(key)?;
+        match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+
+    /// The 64-bit value split across `<key>hi` and `<key>lo`.
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        let hi = self.get_u32(&format!("{}hi", key))?;
+        let lo = self.get_u32(&format!("{}lo", key))?;
+        Some((hi as u64) << 32 | lo as u64)
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, (&'a str, Option<&'a str>)> {
+        self.pairs.iter()
+    }
+}
+
+#[test]
+fn test_params() {
+    let params = Params::parse(r#"name="My Game.xex" sizehi=0x1 sizelo=0x10 count=12 directory"#);
+    assert_eq!(params.get("name"), Some("My Game.xex"));
+    assert_eq!(params.get_u64("size"), Some(0x1_0000_0010));
+    assert_eq!(params.get_u32("COUNT"), Some(12));
+    assert!(params.has("directory"));
+    assert_eq!(params.get("directory"), None);
+    assert_eq!(params.get("missing"), None);
+}
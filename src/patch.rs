@@ -0,0 +1,219 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::io::prelude::*;
+
+use client::Client;
+use error::{Error, Result};
+use memory::parse_hex_line;
+
+/// A replacement of bytes at a fixed address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Patch {
+    pub addr: u32,
+    /// The bytes expected at `addr` before patching, or `None` to patch
+    /// whatever is there.
+    pub original: Option<Vec<u8>>,
+    pub replacement: Vec<u8>,
+}
+
+impl Patch {
+    pub fn new(addr: u32, original: Option<Vec<u8>>, replacement: Vec<u8>) -> Patch {
+        Patch { addr, original, replacement }
+    }
+}
+
+/// A group of patches that are applied and reverted together.
+///
+/// Patch files have one patch per line: the address, the expected original
+/// bytes in hex (or `*` to skip the check) and the replacement bytes in hex.
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// # address   original  replacement
+/// 0x82001234  7c0802a6  60000000
+/// 0x82001238  *         38600001
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchSet {
+    patches: Vec<Patch>,
+    /// The bytes found under each applied patch, in order of application.
+    saved: Vec<Vec<u8>>,
+}
+
+impl PatchSet {
+    pub fn new() -> PatchSet {
+        PatchSet::default()
+    }
+
+    /// Adds `patch` to the end of the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set is applied, or if the original and replacement
+    /// bytes differ in length.
+    pub fn push(&mut self, patch: Patch) {
+        assert!(!self.is_applied(), "cannot add to an applied patch set");
+        assert!(patch.original.as_ref().is_none_or(|o| o.len() == patch.replacement.len()),
+                "original and replacement must be the same length");
+        self.patches.push(patch);
+    }
+
+    pub fn patches(&self) -> &[Patch] { &self.patches }
+
+    /// Whether any patches are currently applied.
+    pub fn is_applied(&self) -> bool { !self.saved.is_empty() }
+
+    /// Checks every patch's original bytes, then writes the replacements in
+    /// order, recording the bytes they overwrite.
+    ///
+    /// Nothing is written if any original does not match; that fails with
+    /// `InvalidData`. If a write fails, the patches already written are
+    /// reverted before the error is returned.
+    pub fn apply(&mut self, client: &mut Client) -> Result<()> {
+        if self.is_applied() {
+            let command = self.patches.first()
+                .map_or_else(String::new, |p| format!("setmem addr=0x{:08x}", p.addr));
+            return Err(Error::io_custom(io::ErrorKind::InvalidInput,
+                "patch set is already applied", command));
+        }
+        let mut current = Vec::with_capacity(self.patches.len());
+        for patch in &self.patches {
+            let bytes = client.read_memory(patch.addr, patch.replacement.len())?;
+            if patch.original.as_ref().is_some_and(|o| *o != bytes) {
+                return Err(Error::io_custom(io::ErrorKind::InvalidData,
+                    format!("original bytes at 0x{:08x} do not match", patch.addr),
+                    format!("setmem addr=0x{:08x}", patch.addr)));
+            }
+            current.push(bytes);
+        }
+        for (patch, bytes) in self.patches.iter().zip(current) {
+            if let Err(e) = client.write_memory_all(patch.addr, &patch.replacement) {
+                // A failed write may have been partial.
+                self.saved.push(bytes);
+                let _ = self.revert(client);
+                return Err(e);
+            }
+            self.saved.push(bytes);
+        }
+        Ok(())
+    }
+
+    /// Restores the recorded bytes of every applied patch, in reverse order.
+    ///
+    /// If a write fails, the patches not yet restored stay applied so that
+    /// `revert` can be retried.
+    pub fn revert(&mut self, client: &mut Client) -> Result<()> {
+        while let Some(bytes) = self.saved.pop() {
+            let addr = self.patches[self.saved.len()].addr;
+            if let Err(e) = client.write_memory_all(addr, &bytes) {
+                self.saved.push(bytes);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads patches in the text format described above.
+    pub fn load<R: BufRead>(reader: R) -> io::Result<PatchSet> {
+        let mut set = PatchSet::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let patch = parse_patch(line).map_err(|desc| io::Error::new(
+                io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, desc)))?;
+            set.patches.push(patch);
+        }
+        Ok(set)
+    }
+
+    /// Writes the patches in the text format described above.
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for patch in &self.patches {
+            let original = match patch.original {
+                Some(ref o) => hex(o),
+                None => "*".to_owned(),
+            };
+            writeln!(writer, "0x{:08x} {} {}", patch.addr, original, hex(&patch.replacement))?;
+        }
+        writer.flush()
+    }
+}
+
+fn parse_patch(line: &str) -> ::std::result::Result<Patch, String> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 3 {
+        return Err("expected an address, original and replacement".to_owned());
+    }
+    let digits = fields[0].strip_prefix("0x").or_else(|| fields[0].strip_prefix("0X"))
+        .unwrap_or(fields[0]);
+    let addr = u32::from_str_radix(digits, 16)
+        .map_err(|_| format!("invalid address '{}'", fields[0]))?;
+    let original = match fields[1] {
+        "*" => None,
+        s => Some(parse_bytes(s)?),
+    };
+    let replacement = parse_bytes(fields[2])?;
+    if replacement.is_empty() {
+        return Err("empty replacement".to_owned());
+    }
+    if original.as_ref().is_some_and(|o| o.len() != replacement.len()) {
+        return Err("original and replacement differ in length".to_owned());
+    }
+    Ok(Patch { addr, original, replacement })
+}
+
+fn parse_bytes(s: &str) -> ::std::result::Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut mapped = Vec::new();
+    parse_hex_line(s, &mut data, &mut mapped).map_err(|e| format!("{} in '{}'", e, s))?;
+    if mapped.iter().any(|&m| !m) {
+        return Err(format!("invalid hex digit in '{}'", s));
+    }
+    Ok(data)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_patch_set() {
+    use client::mock_client;
+    use xbox::Platform;
+
+    let text = "# comment\n0x00001000 aabb 0102\n\n0x00002000 * 03\n";
+    let mut set = PatchSet::load(text.as_bytes()).unwrap();
+    assert_eq!(set.patches()[1], Patch::new(0x2000, None, vec![3]));
+    let mut saved = Vec::new();
+    set.save(&mut saved).unwrap();
+    assert_eq!(String::from_utf8(saved).unwrap(), "0x00001000 aabb 0102\n0x00002000 * 03\n");
+    assert!(PatchSet::load("0x1000 aa 0102".as_bytes()).is_err());
+
+    let (mut client, server) = mock_client(b"\
+        202- multiline response follows\r\naabb\r\n.\r\n\
+        202- multiline response follows\r\n09\r\n.\r\n\
+        200- set 2 bytes\r\n200- set 1 bytes\r\n\
+        200- set 1 bytes\r\n200- set 2 bytes\r\n");
+    client.set_platform(Some(Platform::Classic));
+    set.apply(&mut client).unwrap();
+    assert!(set.is_applied());
+    assert_eq!(set.apply(&mut client).unwrap_err().command(), "setmem addr=0x00001000");
+    set.revert(&mut client).unwrap();
+    assert!(!set.is_applied());
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert!(sent.ends_with("\
+        setmem addr=0x00001000 data=0102\r\n\
+        setmem addr=0x00002000 data=03\r\n\
+        setmem addr=0x00002000 data=09\r\n\
+        setmem addr=0x00001000 data=aabb\r\n"));
+}
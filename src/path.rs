@@ -0,0 +1,421 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::borrow::{Borrow, ToOwned};
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+
+/// The separator between components of a console path.
+const SEPARATOR: char = '\\';
+
+/// The longest file or directory name the console accepts.
+pub(crate) const MAX_FILENAME_LENGTH: usize = 42;
+
+/// Characters the console rejects in file and directory names.
+const INVALID_CHARS: &str = "\"*+,/:;<=>?\\|";
+
+/// A path on the console, such as `E:\Games\Foo\default.xex` or `DEVKIT:\`.
+///
+/// Console paths use `\` as a separator and start with a drive name followed
+/// by `:` when absolute. Comparisons ignore ASCII case and redundant
+/// separators, as the console does.
+///
+/// This is an unsized type like `str`; see [`XboxPathBuf`] for the owned
+/// version.
+///
+/// [`XboxPathBuf`]: struct.XboxPathBuf.html
+#[repr(transparent)]
+pub struct XboxPath {
+    inner: str,
+}
+
+impl XboxPath {
+    pub fn new<S: AsRef<str> + ?Sized>(s: &S) -> &XboxPath {
+        // XboxPath is a transparent wrapper around str.
+        unsafe { &*(s.as_ref() as *const str as *const XboxPath) }
+    }
+
+    pub fn as_str(&self) -> &str { &self.inner }
+
+    pub fn to_path_buf(&self) -> XboxPathBuf {
+        XboxPathBuf { inner: self.inner.to_owned() }
+    }
+
+    /// Splits off the drive name, if the path has one.
+    fn split_drive(&self) -> (Option<&str>, &str) {
+        let first = self.inner.split(SEPARATOR).next().unwrap_or("");
+        match first.find(':') {
+            Some(n) if n + 1 == first.len() => (Some(&first[..n]), &self.inner[n + 1..]),
+            _ => (None, &self.inner),
+        }
+    }
+
+    /// The drive name without its colon, such as `E` or `DEVKIT`.
+    pub fn drive(&self) -> Option<&str> {
+        self.split_drive().0
+    }
+
+    /// Whether the path starts with a drive.
+    pub fn is_absolute(&self) -> bool {
+        self.drive().is_some()
+    }
+
+    /// The file and directory names in the path, excluding the drive.
+    pub fn components(&self) -> impl DoubleEndedIterator<Item=&str> {
+        self.split_drive().1.split(SEPARATOR).filter(|c| !c.is_empty())
+    }
+
+    /// The last component, or `None` for a drive root or empty path.
+    pub fn file_name(&self) -> Option<&str> {
+        self.components().next_back()
+    }
+
+    /// The path without its last component, or `None` for a drive root or
+    /// empty path.
+    pub fn parent(&self) -> Option<&XboxPath> {
+        let name = self.file_name()?;
+        let trimmed = self.inner.trim_end_matches(SEPARATOR);
+        let parent = &trimmed[..trimmed.len() - name.len()];
+        // Keep the separator after a drive, so that `E:\Games` has the
+        // parent `E:\`.
+        let parent = match parent.trim_end_matches(SEPARATOR) {
+            p if p.ends_with(':') && !p.contains(SEPARATOR) => &parent[..p.len() + 1],
+            p => p,
+        };
+        Some(XboxPath::new(parent))
+    }
+
+    /// Appends `path`, or returns it if it is absolute.
+    pub fn join<P: AsRef<XboxPath> + ?Sized>(&self, path: &P) -> XboxPathBuf {
+        let mut buf = self.to_path_buf();
+        buf.push(path);
+        buf
+    }
+
+    /// Checks the path against the rules that make the console fail with
+    /// `InvalidFilename`: each name must be 1 to 42 printable ASCII
+    /// characters without `"*+,/:;<=>?\|`, and must not be `.` or `..`.
+    pub fn validate(&self) -> ::std::result::Result<(), InvalidPathError> {
+        if let Some(drive) = self.drive() {
+            if drive.is_empty() || !drive.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(InvalidPathError(format!("invalid drive '{}'", drive)));
+            }
+        }
+        self.components().try_for_each(check_name)
+    }
+
+    /// Converts the path to a host path, with the drive name, if any, as the
+    /// first directory. `E:\Games\default.xex` becomes `E/Games/default.xex`
+    /// on Unix.
+    pub fn to_host_path(&self) -> PathBuf {
+        self.drive().into_iter().chain(self.components()).collect()
+    }
+}
+
+/// Checks a single file or directory name against the rules in
+/// [`XboxPath::validate`].
+///
+/// [`XboxPath::validate`]: struct.XboxPath.html#method.validate
+fn check_name(name: &str) -> ::std::result::Result<(), InvalidPathError> {
+    if name.len() > MAX_FILENAME_LENGTH {
+        return Err(InvalidPathError(
+            format!("'{}' is longer than {} characters", name, MAX_FILENAME_LENGTH)));
+    }
+    if name == "." || name == ".." {
+        return Err(InvalidPathError(format!("'{}' is not a valid name", name)));
+    }
+    if let Some(c) = name.chars().find(|&c| !(' '..='~').contains(&c) ||
+                                           INVALID_CHARS.contains(c)) {
+        return Err(InvalidPathError(format!("'{}' contains {:?}", name, c)));
+    }
+    Ok(())
+}
+
+impl AsRef<XboxPath> for XboxPath {
+    fn as_ref(&self) -> &XboxPath { self }
+}
+
+impl AsRef<XboxPath> for str {
+    fn as_ref(&self) -> &XboxPath { XboxPath::new(self) }
+}
+
+impl AsRef<XboxPath> for String {
+    fn as_ref(&self) -> &XboxPath { XboxPath::new(self) }
+}
+
+impl AsRef<str> for XboxPath {
+    fn as_ref(&self) -> &str { &self.inner }
+}
+
+impl ToOwned for XboxPath {
+    type Owned = XboxPathBuf;
+    fn to_owned(&self) -> XboxPathBuf { self.to_path_buf() }
+}
+
+impl fmt::Debug for XboxPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+impl fmt::Display for XboxPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl XboxPath {
+    /// The drive and components, lowercased, used for comparisons.
+    fn key(&self) -> impl Iterator<Item=(bool, impl Iterator<Item=u8> + '_)> {
+        let drive = self.drive().map(|d| (true, d));
+        let components = self.components().map(|c| (false, c));
+        drive.into_iter().chain(components)
+            .map(|(is_drive, s)| (is_drive, s.bytes().map(|b| b.to_ascii_lowercase())))
+    }
+}
+
+impl PartialEq for XboxPath {
+    fn eq(&self, other: &XboxPath) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for XboxPath {}
+
+impl PartialOrd for XboxPath {
+    fn partial_cmp(&self, other: &XboxPath) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for XboxPath {
+    fn cmp(&self, other: &XboxPath) -> Ordering {
+        let mut a = self.key();
+        let mut b = other.key();
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some((da, ca)), Some((db, cb))) => {
+                    match db.cmp(&da).then_with(|| ca.cmp(cb)) {
+                        Ordering::Equal => {},
+                        ordering => return ordering,
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Hash for XboxPath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (is_drive, bytes) in self.key() {
+            state.write_u8(is_drive as u8);
+            for b in bytes {
+                state.write_u8(b);
+            }
+            state.write_u8(0xff);
+        }
+    }
+}
+
+/// An owned, mutable console path.
+#[derive(Clone, Default)]
+pub struct XboxPathBuf {
+    inner: String,
+}
+
+impl XboxPathBuf {
+    pub fn new() -> XboxPathBuf {
+        XboxPathBuf::default()
+    }
+
+    pub fn as_path(&self) -> &XboxPath { XboxPath::new(&self.inner) }
+
+    pub fn into_string(self) -> String { self.inner }
+
+    /// Appends `path`, or replaces `self` with it if it is absolute.
+    pub fn push<P: AsRef<XboxPath> + ?Sized>(&mut self, path: &P) {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.inner = path.inner.to_owned();
+            return;
+        }
+        let path = path.inner.trim_start_matches(SEPARATOR);
+        if path.is_empty() {
+            return;
+        }
+        if !self.inner.is_empty() && !self.inner.ends_with(SEPARATOR) {
+            self.inner.push(SEPARATOR);
+        }
+        self.inner.push_str(path);
+    }
+
+    /// Removes the last component, returning false if there was none.
+    pub fn pop(&mut self) -> bool {
+        let len = match self.parent() {
+            Some(parent) => parent.inner.len(),
+            None => return false,
+        };
+        self.inner.truncate(len);
+        true
+    }
+
+    /// Converts a relative host path, such as `Games/Foo/default.xex`, to a
+    /// relative console path. `.` components are skipped, and every other
+    /// component must be a valid console name, so that one such as `E:` or
+    /// `a\b` cannot change the meaning of the path.
+    pub fn from_host_path<P: AsRef<Path>>(path: P)
+        -> ::std::result::Result<XboxPathBuf, InvalidPathError>
+    {
+        let path = path.as_ref();
+        let mut buf = XboxPathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => match name.to_str() {
+                    Some(name) => {
+                        check_name(name)?;
+                        buf.push(name);
+                    },
+                    None => return Err(InvalidPathError(
+                        format!("{:?} is not valid Unicode", name))),
+                },
+                Component::CurDir => {},
+                _ => return Err(InvalidPathError(
+                    format!("{} is not a relative path without '..'", path.display()))),
+            }
+        }
+        Ok(buf)
+    }
+}
+
+impl Deref for XboxPathBuf {
+    type Target = XboxPath;
+    fn deref(&self) -> &XboxPath { self.as_path() }
+}
+
+impl Borrow<XboxPath> for XboxPathBuf {
+    fn borrow(&self) -> &XboxPath { self.as_path() }
+}
+
+impl AsRef<XboxPath> for XboxPathBuf {
+    fn as_ref(&self) -> &XboxPath { self.as_path() }
+}
+
+impl AsRef<str> for XboxPathBuf {
+    fn as_ref(&self) -> &str { &self.inner }
+}
+
+impl<'a> From<&'a str> for XboxPathBuf {
+    fn from(s: &'a str) -> XboxPathBuf {
+        XboxPathBuf { inner: s.to_owned() }
+    }
+}
+
+impl From<String> for XboxPathBuf {
+    fn from(s: String) -> XboxPathBuf {
+        XboxPathBuf { inner: s }
+    }
+}
+
+impl fmt::Debug for XboxPathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_path(), f)
+    }
+}
+
+impl fmt::Display for XboxPathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_path(), f)
+    }
+}
+
+impl PartialEq for XboxPathBuf {
+    fn eq(&self, other: &XboxPathBuf) -> bool { self.as_path() == other.as_path() }
+}
+
+impl Eq for XboxPathBuf {}
+
+impl PartialOrd for XboxPathBuf {
+    fn partial_cmp(&self, other: &XboxPathBuf) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for XboxPathBuf {
+    fn cmp(&self, other: &XboxPathBuf) -> Ordering { self.as_path().cmp(other.as_path()) }
+}
+
+impl Hash for XboxPathBuf {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.as_path().hash(state) }
+}
+
+/// An error returned when a path breaks the console's naming rules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPathError(String);
+
+impl fmt::Display for InvalidPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid path: {}", self.0)
+    }
+}
+
+impl error::Error for InvalidPathError {}
+
+#[test]
+fn test_xbox_path() {
+    let path = XboxPath::new(r"E:\Games\Foo\default.xex");
+    assert_eq!(path.drive(), Some("E"));
+    assert_eq!(path.file_name(), Some("default.xex"));
+    assert_eq!(path.parent().unwrap().as_str(), r"E:\Games\Foo");
+    assert_eq!(XboxPath::new(r"E:\Games").parent().unwrap().as_str(), r"E:\");
+    assert_eq!(XboxPath::new(r"DEVKIT:\").parent(), None);
+    assert_eq!(XboxPath::new(r"DEVKIT:\").drive(), Some("DEVKIT"));
+    assert_eq!(XboxPath::new(r"Foo\bar").drive(), None);
+
+    assert_eq!(XboxPath::new(r"E:\Games").join("Foo").as_str(), r"E:\Games\Foo");
+    assert_eq!(XboxPath::new(r"E:\").join("Foo").as_str(), r"E:\Foo");
+    assert_eq!(XboxPath::new(r"E:\Games").join(r"HDD:\x").as_str(), r"HDD:\x");
+    let mut buf = XboxPathBuf::from(r"E:\Games\Foo");
+    assert!(buf.pop() && buf.pop());
+    assert_eq!(buf.as_str(), r"E:\");
+    assert!(!buf.pop());
+
+    assert_eq!(XboxPath::new(r"e:\GAMES\foo\"), XboxPath::new(r"E:\Games\Foo"));
+    assert_ne!(XboxPath::new(r"E:\Games"), XboxPath::new(r"Games"));
+    assert!(XboxPath::new(r"E:\Games\Foo").validate().is_ok());
+    assert!(XboxPath::new(r"E:\Games\a?b").validate().is_err());
+    assert!(XboxPath::new(&format!(r"E:\{}", "a".repeat(43))).validate().is_err());
+
+    let host = XboxPath::new(r"E:\Games\default.xex").to_host_path();
+    assert_eq!(host, Path::new("E").join("Games").join("default.xex"));
+    assert_eq!(XboxPathBuf::from_host_path("Games/./Foo").unwrap().as_str(), r"Games\Foo");
+    assert!(XboxPathBuf::from_host_path("../Foo").is_err());
+    assert!(XboxPathBuf::from_host_path("Games/E:").is_err());
+    if cfg!(unix) {
+        // `\` is not a separator on Unix, so this is one name.
+        assert!(XboxPathBuf::from_host_path("Games/a\\b").is_err());
+    }
+    assert!(XboxPathBuf::from_host_path("Games/a?b").is_err());
+
+    // A drive must be followed by a separator, so `E:foo` is a single
+    // relative name, and an invalid one.
+    let path = XboxPath::new("E:foo");
+    assert_eq!(path.drive(), None);
+    assert!(!path.is_absolute());
+    assert_eq!(path.components().collect::<Vec<_>>(), vec!["E:foo"]);
+    assert!(path.validate().is_err());
+
+    assert_eq!(XboxPath::new("Foo").parent().unwrap().as_str(), "");
+    assert_eq!(XboxPath::new(r"Foo\").parent().unwrap().as_str(), "");
+    assert_eq!(XboxPath::new("").parent(), None);
+}
@@ -0,0 +1,160 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::io::prelude::*;
+
+use client::{Client, Execute};
+use command::Command;
+use error::{Error, Result};
+use params::Params;
+use status::StatusCode;
+
+/// Page protection flags, using the same values as the Win32 `PAGE_*`
+/// constants.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Protection(pub u32);
+
+impl Protection {
+    pub const NOACCESS: u32 = 0x01;
+    pub const READONLY: u32 = 0x02;
+    pub const READWRITE: u32 = 0x04;
+    pub const WRITECOPY: u32 = 0x08;
+    pub const EXECUTE: u32 = 0x10;
+    pub const EXECUTE_READ: u32 = 0x20;
+    pub const EXECUTE_READWRITE: u32 = 0x40;
+    pub const EXECUTE_WRITECOPY: u32 = 0x80;
+    pub const GUARD: u32 = 0x100;
+    pub const NOCACHE: u32 = 0x200;
+    pub const WRITECOMBINE: u32 = 0x400;
+
+    pub fn bits(&self) -> u32 { self.0 }
+
+    pub fn is_readable(&self) -> bool {
+        self.0 & (Protection::READONLY | Protection::READWRITE | Protection::WRITECOPY |
+                  Protection::EXECUTE_READ | Protection::EXECUTE_READWRITE |
+                  Protection::EXECUTE_WRITECOPY) != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.0 & (Protection::READWRITE | Protection::WRITECOPY |
+                  Protection::EXECUTE_READWRITE | Protection::EXECUTE_WRITECOPY) != 0
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.0 & (Protection::EXECUTE | Protection::EXECUTE_READ |
+                  Protection::EXECUTE_READWRITE | Protection::EXECUTE_WRITECOPY) != 0
+    }
+
+    pub fn is_guard(&self) -> bool { self.0 & Protection::GUARD != 0 }
+    pub fn is_nocache(&self) -> bool { self.0 & Protection::NOCACHE != 0 }
+    pub fn is_writecombine(&self) -> bool { self.0 & Protection::WRITECOMBINE != 0 }
+}
+
+/// Formats the protection as `rwx`, with `-` for missing permissions.
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}",
+               if self.is_readable() { 'r' } else { '-' },
+               if self.is_writable() { 'w' } else { '-' },
+               if self.is_executable() { 'x' } else { '-' })
+    }
+}
+
+/// A region of committed memory reported by `walkmem`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryRegion {
+    pub base: u32,
+    pub size: u32,
+    pub protect: Protection,
+}
+
+impl MemoryRegion {
+    /// The address one past the end of the region.
+    pub fn end(&self) -> u64 {
+        self.base as u64 + self.size as u64
+    }
+
+    pub fn contains(&self, addr: u32) -> bool {
+        addr >= self.base && (addr as u64) < self.end()
+    }
+
+    /// Finds the region containing `addr` in `regions`, which must be sorted
+    /// by base address as returned by [`Client::memory_regions`].
+    ///
+    /// [`Client::memory_regions`]: struct.Client.html#method.memory_regions
+    pub fn find(regions: &[MemoryRegion], addr: u32) -> Option<&MemoryRegion> {
+        let n = regions.partition_point(|r| r.base <= addr);
+        regions[..n].last().filter(|r| r.contains(addr))
+    }
+}
+
+/// Lists committed memory regions with `walkmem`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WalkMem;
+
+impl Command for WalkMem {
+    type Output = Vec<MemoryRegion>;
+
+    fn expect(&self) -> &[StatusCode] {
+        &[StatusCode::MultilineResponseFollows]
+    }
+
+    fn encode(&self) -> String {
+        "walkmem".to_owned()
+    }
+
+    fn decode(self, response: &mut Execute) -> Result<Vec<MemoryRegion>> {
+        let mut regions = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if response.read_line(&mut line).map_err(|e| Error::io(e, response.command()))? == 0 {
+                break;
+            }
+            let params = Params::parse(&line);
+            match (params.get_u32("base"), params.get_u32("size"), params.get_u32("protect")) {
+                (Some(base), Some(size), Some(protect)) => {
+                    regions.push(MemoryRegion { base, size, protect: Protection(protect) });
+                },
+                _ => return Err(Error::bad_response(
+                    format!("invalid region: {}", line.trim_end()), response.command())),
+            }
+        }
+        regions.sort_by_key(|r| r.base);
+        Ok(regions)
+    }
+}
+
+impl Client {
+    /// Lists committed memory regions, sorted by base address.
+    pub fn memory_regions(&mut self) -> Result<Vec<MemoryRegion>> {
+        self.run(WalkMem)
+    }
+
+    /// Finds the committed memory region containing `addr`.
+    pub fn region_containing(&mut self, addr: u32) -> Result<Option<MemoryRegion>> {
+        Ok(MemoryRegion::find(&self.memory_regions()?, addr).cloned())
+    }
+}
+
+#[test]
+fn test_memory_regions() {
+    use client::mock_client;
+
+    let (mut client, _) = mock_client(b"202- multiline response follows\r\n\
+        base=0x82000000 size=0x00010000 protect=0x00000020 phys=0x00000000\r\n\
+        base=0x00010000 size=0x00001000 protect=0x00000204\r\n.\r\n");
+    let regions = client.memory_regions().unwrap();
+    assert_eq!(regions[0], MemoryRegion { base: 0x10000, size: 0x1000, protect: Protection(0x204) });
+    assert_eq!(regions[0].protect.to_string(), "rw-");
+    assert!(regions[0].protect.is_nocache());
+    assert_eq!(regions[1].protect.to_string(), "r-x");
+    assert_eq!(MemoryRegion::find(&regions, 0x8200ffff), Some(&regions[1]));
+    assert_eq!(MemoryRegion::find(&regions, 0x11000), None);
+    assert_eq!(MemoryRegion::find(&regions, 0x100), None);
+}
@@ -0,0 +1,239 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{PORT_360, PORT_CLASSIC};
+use xbox::{MAX_PACKET_LENGTH, parse_reply};
+
+const RELAY_TIMEOUT_MILLIS: u64 = 1000;
+/// How often the relay threads check whether they should stop.
+const STOP_POLL_MILLIS: u64 = 200;
+
+/// Forwards discover and resolve queries to another network segment.
+///
+/// Queries received on the XBDM ports are rebroadcast to the segment's
+/// broadcast address, and replies are sent back to every client that asked
+/// within the last second. Because replies come from the relay, the
+/// console's address is appended to each one; [`discover`] and [`resolve`]
+/// use it in place of the relay's.
+///
+/// [`discover`]: fn.discover.html
+/// [`resolve`]: fn.resolve.html
+#[derive(Clone, Debug)]
+pub struct Relay {
+    listen: Ipv4Addr,
+    forward_from: Ipv4Addr,
+    broadcast: Ipv4Addr,
+}
+
+impl Relay {
+    /// Creates a relay that forwards queries to `broadcast`, the broadcast
+    /// address of the segment the consoles are on.
+    pub fn new(broadcast: Ipv4Addr) -> Relay {
+        Relay {
+            listen: Ipv4Addr::new(0, 0, 0, 0),
+            forward_from: Ipv4Addr::new(0, 0, 0, 0),
+            broadcast,
+        }
+    }
+
+    /// Sets the address queries are accepted on. Note that on most systems a
+    /// socket bound to a unicast address does not receive broadcasts.
+    pub fn listen_on(mut self, ip: Ipv4Addr) -> Relay {
+        self.listen = ip;
+        self
+    }
+
+    /// Sets the local address of the interface on the consoles' segment.
+    pub fn forward_from(mut self, ip: Ipv4Addr) -> Relay {
+        self.forward_from = ip;
+        self
+    }
+
+    /// Relays queries for both platforms until an I/O error occurs.
+    ///
+    /// Failures to send a single query or reply are ignored. When receiving
+    /// fails, every socket is closed before the error is returned, so the
+    /// relay can be run again.
+    pub fn run(&self) -> io::Result<()> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let mut threads = Vec::new();
+        let mut result = Ok(());
+        for &port in &[PORT_360, PORT_CLASSIC] {
+            match self.spawn_port(port, &stop, &tx) {
+                Ok(spawned) => threads.extend(spawned),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                },
+            }
+        }
+        drop(tx);
+        if result.is_ok() {
+            result = rx.recv().unwrap_or(Ok(()));
+        }
+        stop.store(true, Ordering::SeqCst);
+        for thread in threads {
+            let _ = thread.join();
+        }
+        result
+    }
+
+    fn spawn_port(&self, port: u16, stop: &Arc<AtomicBool>, tx: &mpsc::Sender<io::Result<()>>)
+        -> io::Result<Vec<thread::JoinHandle<()>>>
+    {
+        let poll = Some(Duration::from_millis(STOP_POLL_MILLIS));
+        let listen = UdpSocket::bind((self.listen, port))?;
+        listen.set_read_timeout(poll)?;
+        let forward = UdpSocket::bind((self.forward_from, 0))?;
+        forward.set_broadcast(true)?;
+        forward.set_read_timeout(poll)?;
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let replies = {
+            let (listen, forward) = (listen.try_clone()?, forward.try_clone()?);
+            let (pending, stop, tx) = (pending.clone(), stop.clone(), tx.clone());
+            thread::spawn(move || {
+                let _ = tx.send(relay_replies(&forward, &listen, &pending, &stop));
+            })
+        };
+        let broadcast = (self.broadcast, port);
+        let (stop, tx) = (stop.clone(), tx.clone());
+        let queries = thread::spawn(move || {
+            let _ = tx.send(relay_queries(&listen, &forward, broadcast, &pending, &stop));
+        });
+        Ok(vec![replies, queries])
+    }
+}
+
+type Pending = Mutex<Vec<(SocketAddr, Instant)>>;
+
+/// Receives a datagram, returning `None` once `stop` is set.
+fn recv_until_stopped(socket: &UdpSocket, buf: &mut [u8], stop: &AtomicBool)
+    -> io::Result<Option<(usize, SocketAddr)>>
+{
+    while !stop.load(Ordering::SeqCst) {
+        match socket.recv_from(buf) {
+            Ok(x) => return Ok(Some(x)),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                          e.kind() == io::ErrorKind::TimedOut ||
+                          e.kind() == io::ErrorKind::Interrupted ||
+                          // Reported on some systems when an earlier send
+                          // was refused.
+                          e.kind() == io::ErrorKind::ConnectionReset => {},
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+fn relay_queries(listen: &UdpSocket, forward: &UdpSocket, broadcast: (Ipv4Addr, u16),
+                 pending: &Pending, stop: &AtomicBool) -> io::Result<()>
+{
+    let own = forward.local_addr()?;
+    let mut buf = [0; MAX_PACKET_LENGTH];
+    while let Some((n, src)) = recv_until_stopped(listen, &mut buf, stop)? {
+        if is_own(src, own) || n < 2 || (buf[0] != 1 && buf[0] != 3) {
+            continue;
+        }
+        let expires = Instant::now() + Duration::from_millis(RELAY_TIMEOUT_MILLIS);
+        {
+            let mut pending = pending.lock().unwrap();
+            pending.retain(|&(addr, _)| addr != src);
+            pending.push((src, expires));
+        }
+        // A failed broadcast only loses this query, which the client sees as
+        // no console answering before its timeout. It is not worth stopping
+        // the relay for.
+        let _ = forward.send_to(&buf[..n], broadcast);
+    }
+    Ok(())
+}
+
+fn relay_replies(forward: &UdpSocket, listen: &UdpSocket, pending: &Pending, stop: &AtomicBool)
+    -> io::Result<()>
+{
+    let mut buf = [0; MAX_PACKET_LENGTH];
+    while let Some((n, src)) = recv_until_stopped(forward, &mut buf, stop)? {
+        let xbox = match parse_reply(&buf[..n], src) {
+            Some(xbox) => xbox,
+            None => continue,
+        };
+        let end = xbox.name().len() + 2;
+        let mut reply = buf[..end].to_vec();
+        reply.extend_from_slice(&xbox.ip().octets());
+
+        let now = Instant::now();
+        let clients = {
+            let mut pending = pending.lock().unwrap();
+            pending.retain(|&(_, expires)| expires > now);
+            pending.iter().map(|&(addr, _)| addr).collect::<Vec<_>>()
+        };
+        for client in clients {
+            // One unreachable client must not stop replies to the others.
+            let _ = listen.send_to(&reply, client);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a query came from the relay's own forwarding socket, which happens
+/// when both segments share an interface.
+fn is_own(src: SocketAddr, own: SocketAddr) -> bool {
+    src.port() == own.port() && (own.ip() == IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)) ||
+                                 src.ip() == own.ip())
+}
+
+#[test]
+fn test_relay() {
+    // The relay listens on 127.0.0.1 and forwards to a console on 127.0.0.2.
+    // Replies are only recognised from the XBDM ports, so skip the test where
+    // they cannot be bound.
+    let console_ip = Ipv4Addr::new(127, 0, 0, 2);
+    let local = Ipv4Addr::new(127, 0, 0, 1);
+    let console = match UdpSocket::bind((console_ip, PORT_360)) {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    let timeout = Some(Duration::from_secs(5));
+    console.set_read_timeout(timeout).unwrap();
+    let relay = Relay::new(console_ip).listen_on(local).forward_from(local);
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, _rx) = mpsc::channel();
+    let threads = match relay.spawn_port(PORT_360, &stop, &tx) {
+        Ok(threads) => threads,
+        Err(_) => return,
+    };
+
+    let client = UdpSocket::bind((local, 0)).unwrap();
+    client.set_read_timeout(timeout).unwrap();
+    client.send_to(&[3, 0], (local, PORT_360)).unwrap();
+
+    let mut buf = [0; MAX_PACKET_LENGTH];
+    let (n, relay_addr) = console.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..n], &[3, 0]);
+    console.send_to(b"\x02\x03abc", relay_addr).unwrap();
+
+    let (n, src) = client.recv_from(&mut buf).unwrap();
+    assert_eq!(src, SocketAddr::from((local, PORT_360)));
+    assert_eq!(&buf[..n], b"\x02\x03abc\x7f\x00\x00\x02");
+    let xbox = parse_reply(&buf[..n], src).unwrap();
+    assert_eq!((xbox.ip(), xbox.name()), (console_ip, "abc"));
+
+    // Once stopped, the relay's sockets are closed and the port is free.
+    stop.store(true, Ordering::SeqCst);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    UdpSocket::bind((local, PORT_360)).unwrap();
+}
@@ -0,0 +1,186 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use client::Client;
+use memory::MemoryBlock;
+
+/// Size of the console's virtual address space.
+const ADDRESS_SPACE: u64 = 1 << 32;
+
+/// Default size of a cached page.
+pub const DEFAULT_PAGE_SIZE: usize = 0x1000;
+
+/// A cursor over a console's virtual address space.
+///
+/// Reads are served from a cache of whole pages, which is only refreshed on
+/// request with [`refresh`]. Writes go straight to the console and invalidate
+/// the pages they touch. A read stops short at the first unmapped byte, and
+/// fails with `NotFound` if that is the first byte requested.
+///
+/// [`refresh`]: #method.refresh
+#[derive(Debug)]
+pub struct RemoteMemory<'a> {
+    client: &'a mut Client,
+    pos: u64,
+    page_size: usize,
+    cache: HashMap<u32, MemoryBlock>,
+}
+
+impl<'a> RemoteMemory<'a> {
+    /// Creates a cursor positioned at address zero.
+    pub fn new(client: &'a mut Client) -> RemoteMemory<'a> {
+        RemoteMemory {
+            client,
+            pos: 0,
+            page_size: DEFAULT_PAGE_SIZE,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Sets the size of a cached page, which must be a power of two no
+    /// larger than 2 GiB, so that pages fit in the 32-bit address space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `page_size` is not a power of two or is larger than 2 GiB.
+    pub fn with_page_size(mut self, page_size: usize) -> RemoteMemory<'a> {
+        assert!(page_size.is_power_of_two(), "page size must be a power of two");
+        assert!(page_size as u64 <= 1 << 31, "page size must be at most 2 GiB");
+        self.page_size = page_size;
+        self.cache.clear();
+        self
+    }
+
+    /// The current address.
+    pub fn position(&self) -> u64 { self.pos }
+
+    /// Discards every cached page so that the next read fetches fresh data.
+    pub fn refresh(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Discards the cached pages overlapping `len` bytes at `addr`.
+    pub fn invalidate(&mut self, addr: u32, len: usize) {
+        let mask = !(self.page_size as u64 - 1);
+        let start = addr as u64 & mask;
+        let end = cmp::min(addr as u64 + len as u64, ADDRESS_SPACE);
+        let mut page = start;
+        while page < end {
+            self.cache.remove(&(page as u32));
+            page += self.page_size as u64;
+        }
+    }
+
+    pub fn into_inner(self) -> &'a mut Client { self.client }
+
+    fn page(&mut self, addr: u32) -> io::Result<&MemoryBlock> {
+        let page_addr = addr & !(self.page_size as u32 - 1);
+        if !self.cache.contains_key(&page_addr) {
+            let block = self.client.read_memory_block(page_addr, self.page_size)?;
+            self.cache.insert(page_addr, block);
+        }
+        Ok(&self.cache[&page_addr])
+    }
+}
+
+impl<'a> Read for RemoteMemory<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() && self.pos < ADDRESS_SPACE {
+            let addr = self.pos as u32;
+            let copied = {
+                let page = self.page(addr)?;
+                let offset = (addr - page.addr()) as usize;
+                let mapped = page.mapped()[offset..].iter().take_while(|&&m| m).count();
+                let len = cmp::min(mapped, buf.len() - n);
+                buf[n..n + len].copy_from_slice(&page.data()[offset..offset + len]);
+                len
+            };
+            if copied == 0 {
+                if n == 0 {
+                    return Err(io::Error::new(io::ErrorKind::NotFound,
+                        format!("memory not mapped at 0x{:08x}", addr)));
+                }
+                break;
+            }
+            n += copied;
+            self.pos += copied as u64;
+        }
+        Ok(n)
+    }
+}
+
+impl<'a> Write for RemoteMemory<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.pos >= ADDRESS_SPACE || buf.is_empty() {
+            return Ok(0);
+        }
+        let addr = self.pos as u32;
+        let len = cmp::min(buf.len() as u64, ADDRESS_SPACE - self.pos) as usize;
+        self.invalidate(addr, len);
+        let n = self.client.write_memory(addr, &buf[..len])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Seek for RemoteMemory<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+            SeekFrom::End(n) => ADDRESS_SPACE.checked_add_signed(n),
+        };
+        match pos {
+            Some(pos) if pos <= ADDRESS_SPACE => {
+                self.pos = pos;
+                Ok(pos)
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "seek outside the address space")),
+        }
+    }
+}
+
+#[test]
+fn test_remote_memory() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"202- multiline response follows\r\n\
+        00112233????????\r\n.\r\n\
+        200- set 2 bytes\r\n\
+        202- multiline response follows\r\n0011aabb????????\r\n.\r\n");
+    {
+        let mut mem = RemoteMemory::new(&mut client).with_page_size(8);
+        mem.seek(SeekFrom::Start(0x1001)).unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(mem.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], &[0x11, 0x22, 0x33]);
+        assert_eq!(mem.read(&mut buf).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        mem.seek(SeekFrom::Current(-2)).unwrap();
+        mem.write_all(&[0xaa, 0xbb]).unwrap();
+        mem.seek(SeekFrom::Start(0x1000)).unwrap();
+        mem.read_exact(&mut buf[..4]).unwrap();
+        assert_eq!(&buf[..4], &[0x00, 0x11, 0xaa, 0xbb]);
+        assert!(mem.seek(SeekFrom::End(1)).is_err());
+    }
+    drop(client);
+    assert_eq!(server.join().unwrap(), &b"getmem addr=0x00001000 length=0x8\r\n\
+        setmem addr=0x00001002 data=aabb\r\n\
+        getmem addr=0x00001000 length=0x8\r\n"[..]);
+}
@@ -0,0 +1,313 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use config;
+use xbox::{Platform, Xbox, resolve_ip, resolve_name};
+
+/// Environment variable naming the hosts file used by [`resolve`].
+///
+/// [`resolve`]: fn.resolve.html
+pub const HOSTS_VAR: &str = "XBDM_HOSTS";
+
+/// Environment variable naming the console used by [`default_console`].
+///
+/// [`default_console`]: fn.default_console.html
+pub const CONSOLE_VAR: &str = "XBDM_CONSOLE";
+
+const DEFAULT_TTL_SECS: u64 = 300;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct HostEntry {
+    ip: Ipv4Addr,
+    platform: Option<Platform>,
+}
+
+/// Resolves Xbox debug names using a static host map and a cache before
+/// falling back to a broadcast.
+///
+/// Hosts files use the same layout as `/etc/hosts`: an address followed by
+/// one or more debug names, with `#` starting a comment. The address may carry
+/// the XBDM port (`10.0.0.5:730`), in which case the console is never
+/// contacted during resolution. Otherwise its platform is queried directly,
+/// which works across routed networks where broadcasts do not.
+#[derive(Debug)]
+pub struct Resolver {
+    hosts: HashMap<String, HostEntry>,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Xbox, Instant)>>,
+}
+
+impl Resolver {
+    /// Creates a resolver with no static hosts and a five minute cache.
+    pub fn new() -> Resolver {
+        Resolver {
+            hosts: HashMap::new(),
+            ttl: Duration::from_secs(DEFAULT_TTL_SECS),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets how long resolved consoles are cached. A zero TTL disables the
+    /// cache.
+    pub fn with_ttl(mut self, ttl: Duration) -> Resolver {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Adds a static mapping from `name` to `ip`. If `platform` is known, the
+    /// console will not be contacted when `name` is resolved.
+    pub fn insert_host<N>(&mut self, name: N, ip: Ipv4Addr, platform: Option<Platform>)
+        where N: Into<String>
+    {
+        self.hosts.insert(name.into(), HostEntry { ip, platform });
+    }
+
+    /// Adds the static mappings in the hosts file at `path`.
+    pub fn load_hosts<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.read_hosts(io::BufReader::new(File::open(path)?))
+    }
+
+    /// Adds the static mappings read from `reader` in hosts file format.
+    pub fn read_hosts<R: BufRead>(&mut self, reader: R) -> io::Result<()> {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = match line.find('#') {
+                Some(n) => &line[..n],
+                None => &line[..],
+            };
+            let mut fields = line.split_whitespace();
+            let addr = match fields.next() {
+                Some(addr) => addr,
+                None => continue,
+            };
+            let entry = parse_host_addr(addr).ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: invalid address '{}'", i + 1, addr)))?;
+            let mut have_name = false;
+            for name in fields {
+                self.hosts.insert(name.to_owned(), entry);
+                have_name = true;
+            }
+            if !have_name {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: missing name", i + 1)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets all cached resolutions.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Resolve the Xbox debug name or IP address specified by `host`
+    /// as an `Xbox` instance.
+    pub fn resolve(&self, host: &str) -> io::Result<Xbox> {
+        if let Some(xbox) = self.cached(host) {
+            return Ok(xbox);
+        }
+        let xbox = match (host.parse(), self.hosts.get(host)) {
+            (Ok(ip), _) => resolve_ip(ip)?,
+            (_, Some(&HostEntry { ip, platform: Some(platform) })) => {
+                Xbox::new(ip, platform, host)
+            },
+            (_, Some(&HostEntry { ip, platform: None })) => resolve_ip(ip)?,
+            _ => resolve_name(host)?,
+        };
+        if self.ttl > Duration::from_secs(0) {
+            let expires = Instant::now() + self.ttl;
+            self.cache.lock().unwrap().insert(host.to_owned(), (xbox.clone(), expires));
+        }
+        Ok(xbox)
+    }
+
+    fn cached(&self, host: &str) -> Option<Xbox> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(host) {
+            Some(&(ref xbox, expires)) if expires > Instant::now() => {
+                return Some(xbox.clone())
+            },
+            Some(_) => {},
+            None => return None,
+        }
+        cache.remove(host);
+        None
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        Resolver::new()
+    }
+}
+
+fn parse_host_addr(s: &str) -> Option<HostEntry> {
+    if let Ok(ip) = s.parse() {
+        return Some(HostEntry { ip, platform: None });
+    }
+    let addr: SocketAddrV4 = s.parse().ok()?;
+    Some(HostEntry {
+        ip: *addr.ip(),
+        platform: Some(Platform::from_port(addr.port())?),
+    })
+}
+
+/// Loads the hosts file at `path` into `resolver`. A missing file leaves only
+/// the cache and broadcast, which is how resolution behaves without one.
+fn load_default_hosts(resolver: &mut Resolver, path: &Path) -> io::Result<()> {
+    match resolver.load_hosts(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn default_resolver() -> io::Result<&'static Resolver> {
+    static RESOLVER: OnceLock<(Resolver, Option<(io::ErrorKind, String)>)> = OnceLock::new();
+    let (ref resolver, ref error) = *RESOLVER.get_or_init(|| {
+        let mut resolver = Resolver::new();
+        let path = env::var_os(HOSTS_VAR)
+            .map(PathBuf::from)
+            .or_else(|| config::user_file("hosts"));
+        // The error is kept so that every resolution reports it, rather than
+        // quietly ignoring the hosts the user configured.
+        let error = path.and_then(|path| load_default_hosts(&mut resolver, &path).err())
+            .map(|e| (e.kind(), e.to_string()));
+        (resolver, error)
+    });
+    match *error {
+        Some((kind, ref desc)) => Err(io::Error::new(kind, desc.clone())),
+        None => Ok(resolver),
+    }
+}
+
+/// Resolve the Xbox debug name or IP address specified by `host`
+/// as an `Xbox` instance.
+///
+/// Names are looked up in the hosts file named by `XBDM_HOSTS`, or `hosts` in
+/// the per-user configuration directory, before being broadcast. Results are
+/// cached for five minutes; use a [`Resolver`] for control over either. If
+/// the hosts file exists but cannot be read or parsed, every call fails with
+/// its error.
+///
+/// [`Resolver`]: struct.Resolver.html
+pub fn resolve(host: &str) -> io::Result<Xbox> {
+    default_resolver()?.resolve(host)
+}
+
+/// Resolve the default console as an `Xbox` instance.
+///
+/// The target is read from `XBDM_CONSOLE`, or from the first non-comment line
+/// of `console` in the per-user configuration directory, and is a debug name or
+/// IP address optionally followed by `:port`. An error of kind `NotFound` is
+/// returned if neither is set.
+pub fn default_console() -> io::Result<Xbox> {
+    let target = match env::var(CONSOLE_VAR) {
+        Ok(target) => target,
+        Err(env::VarError::NotUnicode(_)) => return Err(io::Error::new(
+            io::ErrorKind::InvalidData, format!("{} is not valid unicode", CONSOLE_VAR))),
+        Err(env::VarError::NotPresent) => read_console_file()?,
+    };
+    let (host, port) = parse_target(&target)?;
+    let xbox = match (host.parse(), port.and_then(Platform::from_port)) {
+        (Ok(ip), Some(platform)) => return Ok(Xbox::new(ip, platform, "")),
+        _ => resolve(host)?,
+    };
+    Ok(match port {
+        Some(port) => xbox.with_port(port),
+        None => xbox,
+    })
+}
+
+fn read_console_file() -> io::Result<String> {
+    let not_configured = || io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no default console configured (set {} or create {})",
+                CONSOLE_VAR, config::user_file("console")
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "a per-user console file".to_owned())));
+    let path = config::user_file("console").ok_or_else(&not_configured)?;
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Err(not_configured()),
+        Err(e) => return Err(e),
+    };
+    for line in io::BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            return Ok(line.to_owned());
+        }
+    }
+    Err(not_configured())
+}
+
+fn parse_target(target: &str) -> io::Result<(&str, Option<u16>)> {
+    let target = target.trim();
+    let invalid = || io::Error::new(
+        io::ErrorKind::InvalidInput, format!("invalid console '{}'", target));
+    let (host, port) = match target.rfind(':') {
+        Some(n) => (&target[..n], Some(target[n+1..].parse().map_err(|_| invalid())?)),
+        None => (target, None),
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host, port))
+}
+
+#[test]
+fn test_parse_target() {
+    assert_eq!(parse_target("MYXBOX").unwrap(), ("MYXBOX", None));
+    assert_eq!(parse_target(" 10.0.0.5:731\n").unwrap(), ("10.0.0.5", Some(731)));
+    assert!(parse_target(":730").is_err());
+    assert!(parse_target("MYXBOX:port").is_err());
+}
+
+#[test]
+fn test_read_hosts() {
+    let hosts = "# lab consoles\n10.0.0.5:730 MYXBOX other\n\n10.0.1.9 CLASSIC # no port\n";
+    let mut resolver = Resolver::new();
+    resolver.read_hosts(io::Cursor::new(hosts)).unwrap();
+    assert_eq!(resolver.hosts["other"], HostEntry {
+        ip: Ipv4Addr::new(10, 0, 0, 5),
+        platform: Some(Platform::Xbox360),
+    });
+    assert_eq!(resolver.hosts["CLASSIC"].platform, None);
+    assert_eq!(resolver.resolve("MYXBOX").unwrap(),
+               Xbox::new(Ipv4Addr::new(10, 0, 0, 5), Platform::Xbox360, "MYXBOX"));
+
+    assert!(resolver.read_hosts(io::Cursor::new("10.0.0.5:80 BAD\n")).is_err());
+    assert!(resolver.read_hosts(io::Cursor::new("10.0.0.5\n")).is_err());
+}
+
+#[test]
+fn test_load_default_hosts() {
+    let dir = env::temp_dir().join(format!("xbdm-hosts-{}", ::std::process::id()));
+    ::std::fs::create_dir_all(&dir).unwrap();
+    let mut resolver = Resolver::new();
+    load_default_hosts(&mut resolver, &dir.join("missing")).unwrap();
+
+    let path = dir.join("hosts");
+    ::std::fs::write(&path, "10.0.0.5:730 MYXBOX\nnot-an-address OTHER\n").unwrap();
+    let err = load_default_hosts(&mut resolver, &path).unwrap_err();
+    ::std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("line 2"));
+}
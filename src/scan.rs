@@ -0,0 +1,244 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use client::Client;
+use error::Result;
+use region::MemoryRegion;
+
+/// Default number of bytes read at a time while scanning.
+pub const DEFAULT_SCAN_CHUNK: usize = 0x10000;
+
+/// A byte signature with wildcards, written in IDA style as `8B 45 ?? 89`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    bytes: Vec<Option<u8>>,
+}
+
+impl Pattern {
+    /// Creates a pattern from bytes, where `None` matches any byte.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is empty.
+    pub fn new(bytes: Vec<Option<u8>>) -> Pattern {
+        assert!(!bytes.is_empty(), "pattern must not be empty");
+        Pattern { bytes }
+    }
+
+    pub fn len(&self) -> usize { self.bytes.len() }
+    pub fn is_empty(&self) -> bool { self.bytes.is_empty() }
+    pub fn bytes(&self) -> &[Option<u8>] { &self.bytes }
+
+    /// Whether the pattern matches the start of `data`.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.bytes.len() &&
+            self.bytes.iter().zip(data).all(|(p, &b)| p.is_none_or(|p| p == b))
+    }
+
+    /// Returns the offset of every match in `data`, including overlapping
+    /// ones.
+    pub fn find_all<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item=usize> + 'a {
+        let end = (data.len() + 1).saturating_sub(self.bytes.len());
+        (0..end).filter(move |&i| self.matches(&data[i..]))
+    }
+}
+
+/// An error returned when parsing a `Pattern` from a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePatternError(String);
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid pattern: {}", self.0)
+    }
+}
+
+impl error::Error for ParsePatternError {}
+
+impl FromStr for Pattern {
+    type Err = ParsePatternError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Pattern, ParsePatternError> {
+        let bytes = s.split_whitespace().map(|token| match token {
+            "?" | "??" => Ok(None),
+            _ if token.len() == 2 => u8::from_str_radix(token, 16).map(Some)
+                .map_err(|_| ParsePatternError(format!("'{}' is not a hex byte", token))),
+            _ => Err(ParsePatternError(format!("'{}' is not a hex byte", token))),
+        }).collect::<::std::result::Result<Vec<_>, _>>()?;
+        if bytes.is_empty() {
+            return Err(ParsePatternError("empty pattern".to_owned()));
+        }
+        Ok(Pattern { bytes })
+    }
+}
+
+type RegionFilter = Box<dyn Fn(&MemoryRegion) -> bool>;
+
+/// Searches console memory for a `Pattern`.
+///
+/// By default every readable committed region reported by `walkmem` is
+/// searched, skipping guard pages. A region that fails to read, other than by
+/// losing the connection, is treated as having no matches. Matches never span
+/// two regions or an unmapped byte.
+pub struct Scanner {
+    pattern: Pattern,
+    range: Option<(u32, u64)>,
+    filter: Option<RegionFilter>,
+    chunk_size: usize,
+}
+
+impl Scanner {
+    pub fn new(pattern: Pattern) -> Scanner {
+        Scanner {
+            pattern,
+            range: None,
+            filter: None,
+            chunk_size: DEFAULT_SCAN_CHUNK,
+        }
+    }
+
+    /// Searches `len` bytes from `addr` instead of the committed regions.
+    pub fn with_range(mut self, addr: u32, len: u64) -> Scanner {
+        self.range = Some((addr, len));
+        self
+    }
+
+    /// Only searches the readable regions for which `filter` returns true,
+    /// such as `|r| r.protect.is_executable()`. Ignored when a range is set.
+    pub fn with_filter<F>(mut self, filter: F) -> Scanner
+        where F: Fn(&MemoryRegion) -> bool + 'static
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets how many bytes are read at a time.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Scanner {
+        self.chunk_size = cmp::max(chunk_size, 1);
+        self
+    }
+
+    pub fn pattern(&self) -> &Pattern { &self.pattern }
+
+    /// Returns the address of every match, in ascending order.
+    pub fn scan(&self, client: &mut Client) -> Result<Vec<u32>> {
+        let segments = match self.range {
+            Some(range) => vec![range],
+            None => client.memory_regions()?.into_iter()
+                .filter(|r| r.protect.is_readable() && !r.protect.is_guard())
+                .filter(|r| self.filter.as_ref().is_none_or(|f| f(r)))
+                .map(|r| (r.base, r.size as u64))
+                .collect(),
+        };
+        let mut matches = Vec::new();
+        for (addr, len) in segments {
+            match self.scan_segment(client, addr, len, &mut matches) {
+                // The region may have been freed or protected since it was
+                // listed.
+                Err(ref e) if self.range.is_none() && !e.is_fatal() => {},
+                result => result?,
+            }
+        }
+        Ok(matches)
+    }
+
+    fn scan_segment(&self, client: &mut Client, addr: u32, len: u64, matches: &mut Vec<u32>)
+        -> Result<()>
+    {
+        let len = cmp::min(len, (1u64 << 32) - addr as u64);
+        // Bytes carried over from the previous chunk so that matches crossing
+        // a chunk boundary are found.
+        let keep = self.pattern.len() - 1;
+        let mut buf = Vec::new();
+        let mut mapped = Vec::new();
+        let mut buf_addr = addr as u64;
+        let mut offset = 0;
+        while offset < len {
+            let n = cmp::min(self.chunk_size as u64, len - offset) as usize;
+            let block = client.read_memory_block((addr as u64 + offset) as u32, n)?;
+            buf.extend_from_slice(block.data());
+            mapped.extend_from_slice(block.mapped());
+            offset += n as u64;
+
+            for i in self.pattern.find_all(&buf) {
+                if mapped[i..i + self.pattern.len()].iter().all(|&m| m) {
+                    matches.push((buf_addr + i as u64) as u32);
+                }
+            }
+            let drain = buf.len().saturating_sub(keep);
+            buf.drain(..drain);
+            mapped.drain(..drain);
+            buf_addr += drain as u64;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Scanner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scanner")
+            .field("pattern", &self.pattern)
+            .field("range", &self.range)
+            .field("filter", &self.filter.as_ref().map(|_| ".."))
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
+
+impl Client {
+    /// Returns the address of every match of `pattern` in committed memory.
+    pub fn scan(&mut self, pattern: &Pattern) -> Result<Vec<u32>> {
+        Scanner::new(pattern.clone()).scan(self)
+    }
+}
+
+#[test]
+fn test_pattern() {
+    let pattern: Pattern = "8B 45 ?? 89".parse().unwrap();
+    assert_eq!(pattern.bytes(), &[Some(0x8b), Some(0x45), None, Some(0x89)]);
+    let data = [0x8b, 0x45, 0x8b, 0x45, 0x00, 0x89, 0x8b, 0x45, 0x01];
+    assert_eq!(pattern.find_all(&data).collect::<Vec<_>>(), vec![2]);
+    assert!("8B 4".parse::<Pattern>().is_err());
+    assert!("".parse::<Pattern>().is_err());
+}
+
+#[test]
+fn test_scan_across_chunks() {
+    use client::mock_client;
+
+    let (mut client, _) = mock_client(b"202- multiline response follows\r\n\
+        base=0x00001000 size=0x00000006 protect=0x00000020\r\n\
+        base=0x00002000 size=0x00000004 protect=0x00000004\r\n.\r\n\
+        202- multiline response follows\r\n00aabb\r\n.\r\n\
+        202- multiline response follows\r\nccaabb\r\n.\r\n");
+    let scanner = Scanner::new("AA BB CC".parse().unwrap())
+        .with_filter(|r| r.protect.is_executable())
+        .with_chunk_size(3);
+    assert_eq!(scanner.scan(&mut client).unwrap(), vec![0x1001]);
+}
+
+#[test]
+fn test_scan_skips_unreadable() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"202- multiline response follows\r\n\
+        base=0x00001000 size=0x00000002 protect=0x00000001\r\n\
+        base=0x00002000 size=0x00000002 protect=0x00000104\r\n\
+        base=0x00003000 size=0x00000002 protect=0x00000004\r\n\
+        base=0x00004000 size=0x00000002 protect=0x00000004\r\n.\r\n\
+        400- unexpected error\r\n\
+        202- multiline response follows\r\naabb\r\n.\r\n");
+    assert_eq!(client.scan(&"AA BB".parse().unwrap()).unwrap(), vec![0x4000]);
+    drop(client);
+    assert_eq!(server.join().unwrap(), &b"walkmem\r\n\
+        getmem addr=0x00003000 length=0x2\r\ngetmem addr=0x00004000 length=0x2\r\n"[..]);
+}
@@ -0,0 +1,278 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::io::prelude::*;
+use std::marker::PhantomData;
+
+use client::Client;
+use error::Result;
+use memory::MemoryBlock;
+use region::MemoryRegion;
+use values::MemoryValue;
+use xbox::Endian;
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"XBDMSNP1";
+
+/// A copy of selected regions of console memory at one point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    endian: Endian,
+    blocks: Vec<MemoryBlock>,
+}
+
+impl Snapshot {
+    /// Reads `regions` from the console. Unmapped bytes are recorded as such
+    /// rather than failing the capture.
+    pub fn capture(client: &mut Client, regions: &[MemoryRegion]) -> Result<Snapshot> {
+        let endian = client.endian()?;
+        let mut blocks = regions.iter()
+            .map(|r| client.read_memory_block(r.base, r.size as usize))
+            .collect::<Result<Vec<_>>>()?;
+        blocks.sort_by_key(|b| b.addr());
+        Ok(Snapshot { endian, blocks })
+    }
+
+    /// The byte order values are decoded with.
+    pub fn endian(&self) -> Endian { self.endian }
+
+    /// The captured blocks, sorted by address.
+    pub fn blocks(&self) -> &[MemoryBlock] { &self.blocks }
+
+    /// The value at `addr`, or `None` if any of its bytes were not captured
+    /// or not mapped.
+    pub fn get<T: MemoryValue>(&self, addr: u32) -> Option<T> {
+        let n = self.blocks.partition_point(|b| b.addr() <= addr);
+        let block = self.blocks[..n].last()?;
+        let offset = (addr - block.addr()) as usize;
+        let end = offset.checked_add(T::SIZE)?;
+        if end > block.data().len() || !block.mapped()[offset..end].iter().all(|&m| m) {
+            return None;
+        }
+        Some(T::from_bytes(&block.data()[offset..end], self.endian))
+    }
+
+    /// Writes the snapshot in a compact binary format.
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&[match self.endian { Endian::Little => 0, Endian::Big => 1 }])?;
+        writer.write_all(&(self.blocks.len() as u32).to_le_bytes())?;
+        for block in &self.blocks {
+            writer.write_all(&block.addr().to_le_bytes())?;
+            writer.write_all(&(block.data().len() as u32).to_le_bytes())?;
+            writer.write_all(block.data())?;
+            let mut mask = vec![0u8; block.data().len().div_ceil(8)];
+            for (i, _) in block.mapped().iter().enumerate().filter(|&(_, &m)| m) {
+                mask[i / 8] |= 1 << (i % 8);
+            }
+            writer.write_all(&mask)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a snapshot written by [`save`].
+    ///
+    /// [`save`]: #method.save
+    pub fn load<R: Read>(mut reader: R) -> io::Result<Snapshot> {
+        let invalid = |desc| io::Error::new(io::ErrorKind::InvalidData, desc);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(invalid("not a snapshot"));
+        }
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        let endian = match byte[0] {
+            0 => Endian::Little,
+            1 => Endian::Big,
+            _ => return Err(invalid("invalid byte order")),
+        };
+        let count = read_u32(&mut reader)?;
+        let mut blocks = Vec::new();
+        for _ in 0..count {
+            let addr = read_u32(&mut reader)?;
+            let len = read_u32(&mut reader)? as usize;
+            let mut data = Vec::new();
+            reader.by_ref().take(len as u64).read_to_end(&mut data)?;
+            let mut mask = Vec::new();
+            reader.by_ref().take(len.div_ceil(8) as u64).read_to_end(&mut mask)?;
+            if data.len() != len || mask.len() != len.div_ceil(8) {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated snapshot"));
+            }
+            let mapped = (0..len).map(|i| mask[i / 8] & (1 << (i % 8)) != 0).collect();
+            blocks.push(MemoryBlock::new(addr, data, mapped));
+        }
+        blocks.sort_by_key(|b| b.addr());
+        Ok(Snapshot { endian, blocks })
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// A condition used to narrow down a [`ValueFinder`].
+///
+/// [`ValueFinder`]: struct.ValueFinder.html
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Predicate<T> {
+    /// The value differs between the two snapshots.
+    Changed,
+    /// The value is the same in both snapshots.
+    Unchanged,
+    /// The value is greater in the newer snapshot.
+    Increased,
+    /// The value is less in the newer snapshot.
+    Decreased,
+    /// The value in the newer snapshot equals this one.
+    Equals(T),
+}
+
+impl<T: PartialOrd> Predicate<T> {
+    fn test(&self, old: T, new: T) -> bool {
+        match *self {
+            Predicate::Changed => new != old,
+            Predicate::Unchanged => new == old,
+            Predicate::Increased => new > old,
+            Predicate::Decreased => new < old,
+            Predicate::Equals(ref v) => new == *v,
+        }
+    }
+}
+
+/// Narrows down the addresses that may hold a value of type `T` by comparing
+/// successive snapshots, as in a cheat finder.
+///
+/// Until the first narrowing step, the candidates are every aligned address
+/// in the first snapshot and are not listed out, so starting a search of all
+/// of memory costs no more than the snapshot itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueFinder<T> {
+    candidates: Candidates,
+    _marker: PhantomData<T>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Candidates {
+    /// Every aligned, fully mapped address in these blocks.
+    Initial { blocks: Vec<MemoryBlock>, alignment: u32 },
+    Listed(Vec<u32>),
+}
+
+impl<T: MemoryValue + PartialOrd> ValueFinder<T> {
+    /// Starts with every `alignment`-aligned address in `snapshot` holding a
+    /// fully mapped value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` is zero.
+    pub fn new(snapshot: &Snapshot, alignment: u32) -> ValueFinder<T> {
+        assert!(alignment > 0, "alignment must not be zero");
+        ValueFinder {
+            candidates: Candidates::Initial { blocks: snapshot.blocks.clone(), alignment },
+            _marker: PhantomData,
+        }
+    }
+
+    /// The addresses still under consideration, in ascending order.
+    pub fn candidates(&self) -> Box<dyn Iterator<Item=u32> + '_> {
+        match self.candidates {
+            Candidates::Initial { ref blocks, alignment } => {
+                Box::new(initial_candidates::<T>(blocks, alignment))
+            },
+            Candidates::Listed(ref list) => Box::new(list.iter().cloned()),
+        }
+    }
+
+    /// The number of addresses still under consideration.
+    pub fn len(&self) -> usize {
+        match self.candidates {
+            Candidates::Initial { .. } => self.candidates().count(),
+            Candidates::Listed(ref list) => list.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates().next().is_none()
+    }
+
+    /// Keeps the candidates for which `predicate` holds between `old` and
+    /// `new`. Candidates missing from either snapshot are dropped.
+    pub fn narrow(&mut self, old: &Snapshot, new: &Snapshot, predicate: Predicate<T>) {
+        self.retain(|addr| match (old.get::<T>(addr), new.get::<T>(addr)) {
+            (Some(o), Some(n)) => predicate.test(o, n),
+            _ => false,
+        });
+    }
+
+    /// Keeps the candidates whose value in `snapshot` equals `value`.
+    pub fn retain_equal(&mut self, snapshot: &Snapshot, value: T) {
+        self.retain(|addr| snapshot.get::<T>(addr) == Some(value));
+    }
+
+    fn retain<F: FnMut(u32) -> bool>(&mut self, mut keep: F) {
+        let list = match self.candidates {
+            Candidates::Listed(ref mut list) => {
+                list.retain(|&addr| keep(addr));
+                return;
+            },
+            Candidates::Initial { ref blocks, alignment } => {
+                initial_candidates::<T>(blocks, alignment).filter(|&addr| keep(addr)).collect()
+            },
+        };
+        self.candidates = Candidates::Listed(list);
+    }
+}
+
+fn initial_candidates<T: MemoryValue>(blocks: &[MemoryBlock], alignment: u32)
+    -> impl Iterator<Item=u32> + '_
+{
+    let alignment = alignment as u64;
+    blocks.iter().flat_map(move |block| {
+        let start = block.addr() as u64;
+        let end = start + block.data().len() as u64;
+        let first = start.div_ceil(alignment) * alignment;
+        (first..(end + 1).saturating_sub(T::SIZE as u64))
+            .step_by(alignment as usize)
+            .filter(move |&addr| {
+                let offset = (addr - start) as usize;
+                block.mapped()[offset..offset + T::SIZE].iter().all(|&m| m)
+            })
+            .map(|addr| addr as u32)
+    })
+}
+
+#[test]
+fn test_value_finder() {
+    use client::mock_client;
+    use region::Protection;
+    use xbox::Platform;
+
+    let (mut client, _) = mock_client(b"\
+        202- multiline response follows\r\n0100000005000000????????\r\n.\r\n\
+        202- multiline response follows\r\n0200000005000000????????\r\n.\r\n");
+    client.set_platform(Some(Platform::Classic));
+    let regions = [MemoryRegion { base: 0x1000, size: 12, protect: Protection(4) }];
+    let before = Snapshot::capture(&mut client, &regions).unwrap();
+    let after = Snapshot::capture(&mut client, &regions).unwrap();
+
+    let mut finder = ValueFinder::<u32>::new(&before, 4);
+    assert_eq!(finder.candidates().collect::<Vec<_>>(), vec![0x1000, 0x1004]);
+    assert_eq!(finder.len(), 2);
+    finder.narrow(&before, &after, Predicate::Increased);
+    assert_eq!(finder.candidates().collect::<Vec<_>>(), vec![0x1000]);
+    finder.retain_equal(&after, 2);
+    assert_eq!(finder.candidates().collect::<Vec<_>>(), vec![0x1000]);
+    assert_eq!(ValueFinder::<u16>::new(&before, 2).len(), 4);
+
+    let mut saved = Vec::new();
+    after.save(&mut saved).unwrap();
+    assert_eq!(Snapshot::load(&saved[..]).unwrap(), after);
+    assert!(Snapshot::load(&saved[..20]).is_err());
+}
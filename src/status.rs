@@ -5,10 +5,22 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::cmp::Ordering;
+use std::error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use error::ErrorCategory;
 
 /// A status code returned by an XBDM command.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Codes compare, order and hash by their numeric value, so `Other(402)` is
+/// equal to `FileNotFound`.
+#[derive(Clone, Debug)]
 pub enum StatusCode {
     /// 200- OK
     Ok,
@@ -22,6 +34,14 @@ pub enum StatusCode {
     SendBinaryData,
     /// 205- connection dedicated
     ConnectionDedicated,
+    /// 206- profile restarted
+    ProfileRestarted,
+    /// 207- fastcap enabled
+    FastCapEnabled,
+    /// 208- callcap enabled
+    CallCapEnabled,
+    /// 209- result code
+    ResultCode,
     /// 400- unexpected error
     UnexpectedError,
     /// 401- max number of connections exceeded
@@ -34,7 +54,7 @@ pub enum StatusCode {
     MemoryNotMapped,
     /// 405- no such thread
     NoSuchThread,
-    /// 406-
+    /// 406- clock not set
     ClockNotSet,
     /// 407- unknown command
     UnknownCommand,
@@ -66,6 +86,20 @@ pub enum StatusCode {
     NeedKeyExchange,
     /// 422- dedicated connection required
     MustBeDedicated,
+    /// 423- invalid argument
+    InvalidArgument,
+    /// 424- profile not started
+    ProfileNotStarted,
+    /// 425- profile in progress
+    ProfileInProgress,
+    /// 426- operation timed out
+    TimedOut,
+    /// 427- no such path
+    NoSuchPath,
+    /// 428- line too long
+    LineTooLong,
+    /// A code this crate does not know about, including any informational
+    /// (3xx) replies.
     Other(u16),
 }
 
@@ -78,6 +112,10 @@ impl StatusCode {
             203 => StatusCode::BinaryResponseFollows,
             204 => StatusCode::SendBinaryData,
             205 => StatusCode::ConnectionDedicated,
+            206 => StatusCode::ProfileRestarted,
+            207 => StatusCode::FastCapEnabled,
+            208 => StatusCode::CallCapEnabled,
+            209 => StatusCode::ResultCode,
             400 => StatusCode::UnexpectedError,
             401 => StatusCode::MaxConnectionsExceeded,
             402 => StatusCode::FileNotFound,
@@ -100,6 +138,12 @@ impl StatusCode {
             420 => StatusCode::NotLocked,
             421 => StatusCode::NeedKeyExchange,
             422 => StatusCode::MustBeDedicated,
+            423 => StatusCode::InvalidArgument,
+            424 => StatusCode::ProfileNotStarted,
+            425 => StatusCode::ProfileInProgress,
+            426 => StatusCode::TimedOut,
+            427 => StatusCode::NoSuchPath,
+            428 => StatusCode::LineTooLong,
             _ => StatusCode::Other(n),
         }
     }
@@ -112,6 +156,10 @@ impl StatusCode {
             StatusCode::BinaryResponseFollows => 203,
             StatusCode::SendBinaryData => 204,
             StatusCode::ConnectionDedicated => 205,
+            StatusCode::ProfileRestarted => 206,
+            StatusCode::FastCapEnabled => 207,
+            StatusCode::CallCapEnabled => 208,
+            StatusCode::ResultCode => 209,
             StatusCode::UnexpectedError => 400,
             StatusCode::MaxConnectionsExceeded => 401,
             StatusCode::FileNotFound => 402,
@@ -134,10 +182,25 @@ impl StatusCode {
             StatusCode::NotLocked => 420,
             StatusCode::NeedKeyExchange => 421,
             StatusCode::MustBeDedicated => 422,
+            StatusCode::InvalidArgument => 423,
+            StatusCode::ProfileNotStarted => 424,
+            StatusCode::ProfileInProgress => 425,
+            StatusCode::TimedOut => 426,
+            StatusCode::NoSuchPath => 427,
+            StatusCode::LineTooLong => 428,
             StatusCode::Other(n) => n,
         }
     }
 
+    /// The code with its default message, as in `402- file not found`.
+    /// Unknown codes are described by the number alone.
+    pub fn description(&self) -> String {
+        match self.default_message() {
+            Some(msg) => format!("{}- {}", self.to_u16(), msg),
+            None => self.to_u16().to_string(),
+        }
+    }
+
     pub fn default_message(&self) -> Option<&'static str> {
         match *self {
             StatusCode::Ok => Some("OK"),
@@ -146,13 +209,17 @@ impl StatusCode {
             StatusCode::BinaryResponseFollows => Some("binary response follows"),
             StatusCode::SendBinaryData => Some("send binary data"),
             StatusCode::ConnectionDedicated => Some("connection dedicated"),
+            StatusCode::ProfileRestarted => Some("profile restarted"),
+            StatusCode::FastCapEnabled => Some("fastcap enabled"),
+            StatusCode::CallCapEnabled => Some("callcap enabled"),
+            StatusCode::ResultCode => Some("result code"),
             StatusCode::UnexpectedError => Some("unexpected error"),
             StatusCode::MaxConnectionsExceeded => Some("max number of connections exceeded"),
             StatusCode::FileNotFound => Some("file not found"),
             StatusCode::NoSuchModule => Some("no such module"),
             StatusCode::MemoryNotMapped => Some("memory not mapped"),
             StatusCode::NoSuchThread => Some("no such thread"),
-            StatusCode::ClockNotSet => None,
+            StatusCode::ClockNotSet => Some("clock not set"),
             StatusCode::UnknownCommand => Some("unknown command"),
             StatusCode::NotStopped => Some("not stopped"),
             StatusCode::MustCopy => Some("file must be copied"),
@@ -168,14 +235,59 @@ impl StatusCode {
             StatusCode::NotLocked => Some("box not locked"),
             StatusCode::NeedKeyExchange => Some("key exchange required"),
             StatusCode::MustBeDedicated => Some("dedicated connection required"),
-            _ => None,
+            StatusCode::InvalidArgument => Some("invalid argument"),
+            StatusCode::ProfileNotStarted => Some("profile not started"),
+            StatusCode::ProfileInProgress => Some("profile in progress"),
+            StatusCode::TimedOut => Some("operation timed out"),
+            StatusCode::NoSuchPath => Some("no such path"),
+            StatusCode::LineTooLong => Some("line too long"),
+            StatusCode::Other(_) => None,
         }
     }
 
+    /// Classifies a failure code, or returns `None` for a success code.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        if self.is_success() {
+            return None;
+        }
+        Some(match *self {
+            StatusCode::MaxConnectionsExceeded => ErrorCategory::Busy,
+            StatusCode::FileNotFound |
+            StatusCode::NoSuchModule |
+            StatusCode::NoSuchThread |
+            StatusCode::NoCounterData |
+            StatusCode::NoSuchPath => ErrorCategory::NotFound,
+            StatusCode::MemoryNotMapped => ErrorCategory::NotMapped,
+            StatusCode::UnknownCommand |
+            StatusCode::MustCopy |
+            StatusCode::NotDebuggable => ErrorCategory::Unsupported,
+            StatusCode::NotStopped => ErrorCategory::NotStopped,
+            StatusCode::FileExists => ErrorCategory::AlreadyExists,
+            StatusCode::NotEmpty => ErrorCategory::NotEmpty,
+            StatusCode::InvalidFilename |
+            StatusCode::InvalidCounterType |
+            StatusCode::InvalidArgument |
+            StatusCode::LineTooLong => ErrorCategory::InvalidInput,
+            StatusCode::CannotCreate |
+            StatusCode::AccessDenied |
+            StatusCode::NotLocked |
+            StatusCode::NeedKeyExchange => ErrorCategory::PermissionDenied,
+            StatusCode::DeviceFull => ErrorCategory::StorageFull,
+            StatusCode::TimedOut => ErrorCategory::TimedOut,
+            _ => ErrorCategory::Other,
+        })
+    }
+
     pub fn is_success(&self) -> bool {
         self.to_u16() < 400
     }
 
+    /// Whether this is an informational (3xx) reply, which the console may
+    /// send ahead of a command's final response.
+    pub fn is_informational(&self) -> bool {
+        (300..400).contains(&self.to_u16())
+    }
+
     pub fn is_failure(&self) -> bool {
         self.to_u16() >= 400
     }
@@ -183,12 +295,81 @@ impl StatusCode {
 
 impl Copy for StatusCode {}
 
+impl PartialEq for StatusCode {
+    fn eq(&self, other: &StatusCode) -> bool {
+        self.to_u16() == other.to_u16()
+    }
+}
+
+impl Eq for StatusCode {}
+
+impl PartialOrd for StatusCode {
+    fn partial_cmp(&self, other: &StatusCode) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StatusCode {
+    fn cmp(&self, other: &StatusCode) -> Ordering {
+        self.to_u16().cmp(&other.to_u16())
+    }
+}
+
+impl Hash for StatusCode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_u16().hash(state)
+    }
+}
+
 impl fmt::Display for StatusCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.to_u16())
     }
 }
 
+/// An error returned when parsing a `StatusCode` from a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseStatusCodeError(());
+
+impl fmt::Display for ParseStatusCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid status code")
+    }
+}
+
+impl error::Error for ParseStatusCodeError {}
+
+/// Parses a three digit code, optionally followed by `-` and a message as in
+/// a response line.
+impl FromStr for StatusCode {
+    type Err = ParseStatusCodeError;
+
+    fn from_str(s: &str) -> Result<StatusCode, ParseStatusCodeError> {
+        let digits = match s.find('-') {
+            Some(n) => &s[..n],
+            None => s,
+        };
+        if digits.len() != 3 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseStatusCodeError(()));
+        }
+        digits.parse().map(StatusCode::from_u16).map_err(|_| ParseStatusCodeError(()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for StatusCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.to_u16())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for StatusCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<StatusCode, D::Error> {
+        u16::deserialize(deserializer).map(StatusCode::from_u16)
+    }
+}
+
 impl IntoIterator for StatusCode {
     type Item = StatusCode;
     type IntoIter = ::std::option::IntoIter<StatusCode>;
@@ -197,3 +378,22 @@ impl IntoIterator for StatusCode {
         Some(self).into_iter()
     }
 }
+
+#[test]
+fn test_status_code() {
+    assert_eq!(StatusCode::FileNotFound.to_string(), "402");
+    assert_eq!(StatusCode::FileNotFound.description(), "402- file not found");
+    assert_eq!(StatusCode::Other(499).description(), "499");
+    assert_eq!("402".parse(), Ok(StatusCode::FileNotFound));
+    assert_eq!("423- invalid argument".parse(), Ok(StatusCode::InvalidArgument));
+    assert_eq!("42".parse::<StatusCode>(), Err(ParseStatusCodeError(())));
+    assert_eq!(StatusCode::Other(402), StatusCode::FileNotFound);
+    assert!(StatusCode::Ok < StatusCode::Other(301));
+    assert!(StatusCode::Other(301) < StatusCode::UnexpectedError);
+    assert!(StatusCode::Other(301).is_informational());
+    assert!(!StatusCode::Ok.is_informational());
+    assert_eq!("426".parse(), Ok(StatusCode::TimedOut));
+    assert_eq!(StatusCode::TimedOut.category(), Some(ErrorCategory::TimedOut));
+    assert_eq!(StatusCode::NoSuchPath.category(), Some(ErrorCategory::NotFound));
+    assert_eq!(StatusCode::LineTooLong.category(), Some(ErrorCategory::InvalidInput));
+}
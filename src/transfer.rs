@@ -0,0 +1,706 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+use client::Client;
+use error::{Error, ErrorCategory, Result};
+use fs::check_quoted;
+use path::{MAX_FILENAME_LENGTH, XboxPath};
+use status::StatusCode;
+
+/// Number of bytes copied between progress reports.
+const TRANSFER_CHUNK: usize = 0x10000;
+
+/// Default number of bytes sent by each `writefile` of a resumable upload.
+pub const DEFAULT_RESUME_CHUNK: u64 = 0x100_0000;
+
+/// Appended to the remote name of an upload written under a temporary name.
+pub const TEMP_SUFFIX: &str = ".part";
+
+/// Appended to the name of a file while an upload written under a temporary
+/// name replaces it.
+const OLD_SUFFIX: &str = ".old";
+
+/// `remote` with `suffix` appended to its file name, which is shortened first
+/// if the result would be longer than the console allows.
+fn suffixed_name(remote: &str, suffix: &str) -> String {
+    let name = XboxPath::new(remote).file_name().unwrap_or("");
+    let trimmed = remote.trim_end_matches('\\');
+    let dir = &trimmed[..trimmed.len() - name.len()];
+    let max = MAX_FILENAME_LENGTH.saturating_sub(suffix.len());
+    let end = name.char_indices().map(|(i, c)| i + c.len_utf8())
+        .take_while(|&end| end <= max)
+        .last()
+        .unwrap_or(0);
+    format!("{}{}{}", dir, &name[..end], suffix)
+}
+
+/// How far a file transfer has got, as passed to progress callbacks.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// Bytes transferred so far.
+    pub transferred: u64,
+    /// Bytes to transfer in total.
+    pub total: u64,
+    /// Time since the transfer started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The average throughput so far, in bytes per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.transferred as f64 / secs } else { 0.0 }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.transferred >= self.total
+    }
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// Downloads a file from the console with `getfile`, streaming it to a
+/// writer.
+pub struct Download<'a> {
+    remote: String,
+    range: Option<(u64, u64)>,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> Download<'a> {
+    /// Creates a download of the whole of `remote`, such as
+    /// `E:\Games\default.xex`.
+    pub fn new(remote: &str) -> Download<'a> {
+        Download {
+            remote: remote.to_owned(),
+            range: None,
+            progress: None,
+        }
+    }
+
+    /// Only downloads `size` bytes starting at `offset`.
+    pub fn with_range(mut self, offset: u64, size: u64) -> Download<'a> {
+        self.range = Some((offset, size));
+        self
+    }
+
+    /// Calls `progress` after each chunk is written.
+    pub fn with_progress<F>(mut self, progress: F) -> Download<'a>
+        where F: FnMut(&Progress) + 'a
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    fn encode(&self) -> String {
+        match self.range {
+            Some((offset, size)) => format!("getfile name=\"{}\" offset=0x{:x} size=0x{:x}",
+                                            self.remote, offset, size),
+            None => format!("getfile name=\"{}\"", self.remote),
+        }
+    }
+
+    /// Downloads the file into `writer` and returns the number of bytes
+    /// written.
+    ///
+    /// If `writer` fails, the rest of the file is skipped so that the
+    /// connection stays usable, and the writer's error is returned as
+    /// [`ErrorKind::Local`].
+    ///
+    /// [`ErrorKind::Local`]: enum.ErrorKind.html#variant.Local
+    pub fn run<W: Write + ?Sized>(mut self, client: &mut Client, writer: &mut W) -> Result<u64> {
+        let command = self.encode();
+        check_quoted(&self.remote, &command)?;
+        let mut exec = client.execute(StatusCode::BinaryResponseFollows, &command)?;
+        let mut header = [0; 4];
+        if let Err(e) = exec.read_exact(&mut header) {
+            let _ = exec.finish();
+            return Err(Error::io(e, &command[..]));
+        }
+        let total = u32::from_le_bytes(header) as u64;
+        exec.set_limit(total);
+
+        let start = Instant::now();
+        let mut buf = vec![0; cmp::min(total, TRANSFER_CHUNK as u64) as usize];
+        let mut transferred = 0;
+        while transferred < total {
+            let n = match exec.read(&mut buf) {
+                Ok(0) => {
+                    let _ = exec.finish();
+                    return Err(Error::io_custom(io::ErrorKind::UnexpectedEof,
+                        "file ended early", &command[..]));
+                },
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let _ = exec.finish();
+                    return Err(Error::io(e, &command[..]));
+                },
+            };
+            if let Err(e) = writer.write_all(&buf[..n]) {
+                exec.finish()?;
+                return Err(Error::local(e, &command[..]));
+            }
+            transferred += n as u64;
+            if let Some(ref mut progress) = self.progress {
+                progress(&Progress { transferred, total, elapsed: start.elapsed() });
+            }
+        }
+        exec.finish()?;
+        Ok(transferred)
+    }
+}
+
+impl<'a> fmt::Debug for Download<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Download")
+            .field("remote", &self.remote)
+            .field("range", &self.range)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Uploads a file to the console with `sendfile`, streaming it from a
+/// reader.
+///
+/// Failures to create or write the file are reported with their status
+/// codes, such as `CannotCreate`, `AccessDenied` or `DeviceFull`.
+pub struct Upload<'a> {
+    remote: String,
+    temp_name: bool,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> Upload<'a> {
+    /// Creates an upload to `remote`, such as `E:\Games\default.xex`.
+    pub fn new(remote: &str) -> Upload<'a> {
+        Upload {
+            remote: remote.to_owned(),
+            temp_name: false,
+            progress: None,
+        }
+    }
+
+    /// Writes the file under a temporary name ending in [`TEMP_SUFFIX`] and
+    /// renames it over `remote` only once it has been sent in full. If the
+    /// upload fails, the temporary file is deleted and `remote` is left as
+    /// it was. The file name is shortened before the suffix is added if the
+    /// console would otherwise reject it as too long.
+    ///
+    /// An existing `remote` is first renamed aside with `.old` appended, and
+    /// only deleted once the new file is in place, so that one of the two
+    /// always exists. If that fails part way, the old file is left under the
+    /// `.old` name.
+    ///
+    /// [`TEMP_SUFFIX`]: constant.TEMP_SUFFIX.html
+    pub fn with_temp_name(mut self) -> Upload<'a> {
+        self.temp_name = true;
+        self
+    }
+
+    /// Calls `progress` after each chunk is sent.
+    pub fn with_progress<F>(mut self, progress: F) -> Upload<'a>
+        where F: FnMut(&Progress) + 'a
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Sends `len` bytes from `reader`.
+    ///
+    /// If `reader` fails or ends early, the rest of the file is padded with
+    /// zeros so that the connection stays usable, the incomplete file is
+    /// deleted and the reader's error is returned as [`ErrorKind::Local`].
+    ///
+    /// [`ErrorKind::Local`]: enum.ErrorKind.html#variant.Local
+    pub fn run<R: Read + ?Sized>(mut self, client: &mut Client, reader: &mut R, len: u64)
+        -> Result<()>
+    {
+        let name = if self.temp_name {
+            suffixed_name(&self.remote, TEMP_SUFFIX)
+        } else {
+            self.remote.clone()
+        };
+        let command = format!("sendfile name=\"{}\" length=0x{:x}", name, len);
+        check_quoted(&name, &command)?;
+        let start = Instant::now();
+        let progress = &mut self.progress;
+        let result = send_binary(client, &command, reader, len, |transferred| {
+            if let Some(ref mut progress) = *progress {
+                progress(&Progress { transferred, total: len, elapsed: start.elapsed() });
+            }
+        });
+        let error = match result {
+            Ok(None) => None,
+            // The transfer was completed with padding, so the connection can
+            // still be used to clean up, and the padded file must go even if
+            // it is not a temporary one.
+            Ok(Some(e)) => Some((Error::local(e, command), true)),
+            Err(e) => {
+                let usable = self.temp_name && !e.is_fatal();
+                Some((e, usable))
+            },
+        };
+        if let Some((e, remove)) = error {
+            if remove {
+                let _ = client.remove_file(&name);
+            }
+            return Err(e);
+        }
+        if !self.temp_name {
+            return Ok(());
+        }
+        match client.rename(&name, &self.remote) {
+            Err(ref e) if e.category() == Some(ErrorCategory::AlreadyExists) => {
+                let old = suffixed_name(&self.remote, OLD_SUFFIX);
+                client.rename(&self.remote, &old)?;
+                if let Err(e) = client.rename(&name, &self.remote) {
+                    let _ = client.rename(&old, &self.remote);
+                    return Err(e);
+                }
+                client.remove_file(&old)
+            },
+            result => result,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Upload<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Upload")
+            .field("remote", &self.remote)
+            .field("temp_name", &self.temp_name)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Uploads a file to the console in pieces with `writefile`, continuing from
+/// however much of it is already there.
+///
+/// If an upload is interrupted, running it again sends only the rest of the
+/// file. Each piece is written separately, so at most one piece is lost when
+/// the connection drops.
+pub struct ResumableUpload<'a> {
+    remote: String,
+    verify: bool,
+    chunk_size: u64,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> ResumableUpload<'a> {
+    pub fn new(remote: &str) -> ResumableUpload<'a> {
+        ResumableUpload {
+            remote: remote.to_owned(),
+            verify: false,
+            chunk_size: DEFAULT_RESUME_CHUNK,
+            progress: None,
+        }
+    }
+
+    /// Downloads the part of the file already on the console and compares it
+    /// with the local file, resuming from the first difference instead of
+    /// from the end of the remote file.
+    pub fn with_verify(mut self) -> ResumableUpload<'a> {
+        self.verify = true;
+        self
+    }
+
+    /// Sets how many bytes each `writefile` sends.
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> ResumableUpload<'a> {
+        self.chunk_size = cmp::max(chunk_size, 1);
+        self
+    }
+
+    /// Calls `progress` after each chunk is sent. The progress counts the
+    /// bytes already on the console as transferred.
+    pub fn with_progress<F>(mut self, progress: F) -> ResumableUpload<'a>
+        where F: FnMut(&Progress) + 'a
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Uploads `len` bytes from the start of `reader` and returns the offset
+    /// the upload resumed from.
+    pub fn run<R: Read + Seek + ?Sized>(mut self, client: &mut Client, reader: &mut R, len: u64)
+        -> Result<u64>
+    {
+        check_quoted(&self.remote, &format!("writefile name=\"{}\"", self.remote))?;
+        let existing = match remote_size(client, &self.remote)? {
+            Some(size) => size,
+            None => {
+                // Create the file so that it can be written to.
+                let command = format!("sendfile name=\"{}\" length=0x0", self.remote);
+                send_binary(client, &command, &mut io::empty(), 0, |_| {})?;
+                0
+            },
+        };
+        let mut offset = cmp::min(existing, len);
+        if self.verify && offset > 0 {
+            offset = self.verify_prefix(client, reader, offset)?;
+        }
+        reader.seek(io::SeekFrom::Start(offset))
+            .map_err(|e| Error::local(e, format!("writefile name=\"{}\"", self.remote)))?;
+
+        let resumed = offset;
+        let start = Instant::now();
+        while offset < len {
+            let n = cmp::min(self.chunk_size, len - offset);
+            let command = format!("writefile name=\"{}\" offset=0x{:x} length=0x{:x}",
+                                  self.remote, offset, n);
+            let progress = &mut self.progress;
+            let read_error = send_binary(client, &command, reader, n, |transferred| {
+                if let Some(ref mut progress) = *progress {
+                    progress(&Progress {
+                        transferred: offset + transferred,
+                        total: len,
+                        elapsed: start.elapsed(),
+                    });
+                }
+            })?;
+            if let Some(e) = read_error {
+                // Drop the padding so that it is not mistaken for file data
+                // when the upload is resumed.
+                let _ = set_file_size(client, &self.remote, offset);
+                return Err(Error::local(e, command));
+            }
+            offset += n;
+        }
+        if existing != len {
+            set_file_size(client, &self.remote, len)?;
+        }
+        Ok(resumed)
+    }
+
+    /// Returns the length of the prefix of the remote file that matches
+    /// `reader`, comparing up to `len` bytes.
+    fn verify_prefix<R: Read + Seek + ?Sized>(&self, client: &mut Client, reader: &mut R, len: u64)
+        -> Result<u64>
+    {
+        struct Compare<'r, R: 'r + ?Sized> {
+            reader: &'r mut R,
+            buf: Vec<u8>,
+            matched: u64,
+            differs: bool,
+        }
+
+        impl<'r, R: Read + ?Sized> Write for Compare<'r, R> {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                if !self.differs {
+                    self.buf.resize(data.len(), 0);
+                    self.reader.read_exact(&mut self.buf)?;
+                    match self.buf.iter().zip(data).position(|(a, b)| a != b) {
+                        Some(i) => {
+                            self.matched += i as u64;
+                            self.differs = true;
+                        },
+                        None => self.matched += data.len() as u64,
+                    }
+                }
+                Ok(data.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+
+        reader.seek(io::SeekFrom::Start(0))
+            .map_err(|e| Error::local(e, format!("getfile name=\"{}\"", self.remote)))?;
+        let mut compare = Compare { reader, buf: Vec::new(), matched: 0, differs: false };
+        // A failure to read `reader` comes back from the download as a local
+        // error, with the rest of the remote data skipped.
+        Download::new(&self.remote).with_range(0, len).run(client, &mut compare)?;
+        Ok(compare.matched)
+    }
+}
+
+impl<'a> fmt::Debug for ResumableUpload<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResumableUpload")
+            .field("remote", &self.remote)
+            .field("verify", &self.verify)
+            .field("chunk_size", &self.chunk_size)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// The size of `name`, or `None` if it does not exist.
+fn remote_size(client: &mut Client, name: &str) -> Result<Option<u64>> {
+    match client.file_attributes(name) {
+        Ok(attributes) => Ok(Some(attributes.size)),
+        Err(ref e) if e.category() == Some(ErrorCategory::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn set_file_size(client: &mut Client, name: &str, size: u64) -> Result<()> {
+    run_simple(client, format!("fileeof name=\"{}\" size=0x{:x}", name, size))
+}
+
+/// Runs a command that is answered with `SendBinaryData` and sends `len`
+/// bytes from `reader`, calling `sent` with the running total after each
+/// chunk.
+///
+/// If `reader` fails or ends early, the rest is padded with zeros to keep the
+/// connection usable and the reader's error is returned in `Ok`.
+fn send_binary<R, F>(client: &mut Client, command: &str, reader: &mut R, len: u64, mut sent: F)
+    -> Result<Option<io::Error>>
+    where R: Read + ?Sized, F: FnMut(u64)
+{
+    let mut exec = client.execute(StatusCode::SendBinaryData, command)?;
+    exec.set_limit(len);
+
+    let mut buf = vec![0; cmp::min(len, TRANSFER_CHUNK as u64) as usize];
+    let mut transferred = 0;
+    let mut read_error = None;
+    while transferred < len {
+        let max = cmp::min(len - transferred, buf.len() as u64) as usize;
+        let n = match reader.read(&mut buf[..max]) {
+            Ok(0) => {
+                read_error = Some(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                 "reader ended early"));
+                break;
+            },
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                read_error = Some(e);
+                break;
+            },
+        };
+        if let Err(e) = exec.write_all(&buf[..n]) {
+            let _ = exec.finish();
+            return Err(Error::io(e, command));
+        }
+        transferred += n as u64;
+        sent(transferred);
+    }
+    if read_error.is_some() {
+        if let Err(e) = io::copy(&mut io::repeat(0).take(len - transferred), &mut exec) {
+            let _ = exec.finish();
+            return Err(Error::io(e, command));
+        }
+    }
+    exec.finish()?;
+    Ok(read_error)
+}
+
+fn run_simple(client: &mut Client, command: String) -> Result<()> {
+    client.execute(StatusCode::Ok, &command)?.finish().map(|_| ())
+}
+
+impl Client {
+    /// Downloads the whole of `remote` into `writer` and returns the number
+    /// of bytes written. Use [`Download`] for ranges and progress reports.
+    ///
+    /// [`Download`]: struct.Download.html
+    pub fn download<W: Write + ?Sized>(&mut self, remote: &str, writer: &mut W) -> Result<u64> {
+        Download::new(remote).run(self, writer)
+    }
+
+    /// Uploads `len` bytes from `reader` to `remote`. Use [`Upload`] to
+    /// upload under a temporary name or report progress.
+    ///
+    /// [`Upload`]: struct.Upload.html
+    pub fn upload<R: Read + ?Sized>(&mut self, reader: &mut R, remote: &str, len: u64)
+        -> Result<()>
+    {
+        Upload::new(remote).run(self, reader, len)
+    }
+
+    /// Uploads `len` bytes from `reader` to `remote`, sending only what is
+    /// not already there, and returns the offset the upload resumed from.
+    /// Use [`ResumableUpload`] to verify the existing data or report
+    /// progress.
+    ///
+    /// [`ResumableUpload`]: struct.ResumableUpload.html
+    pub fn upload_resumable<R: Read + Seek + ?Sized>(&mut self, reader: &mut R, remote: &str,
+                                                     len: u64) -> Result<u64>
+    {
+        ResumableUpload::new(remote).run(self, reader, len)
+    }
+}
+
+#[test]
+fn test_download() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"\
+        203- binary response follows\r\n\x05\x00\x00\x00hello\
+        203- binary response follows\r\n\x02\x00\x00\x00lo\
+        200- OK\r\n");
+    let mut data = Vec::new();
+    assert_eq!(client.download(r"E:\a.txt", &mut data).unwrap(), 5);
+    assert_eq!(data, b"hello");
+
+    let mut reports = Vec::new();
+    let mut data = Vec::new();
+    Download::new(r"E:\a.txt")
+        .with_range(3, 2)
+        .with_progress(|p| reports.push((p.transferred, p.total)))
+        .run(&mut client, &mut data)
+        .unwrap();
+    assert_eq!(data, b"lo");
+    assert_eq!(reports, vec![(2, 2)]);
+
+    client.execute(StatusCode::Ok, "noop").unwrap().finish().unwrap();
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert_eq!(sent, "getfile name=\"E:\\a.txt\"\r\n\
+                      getfile name=\"E:\\a.txt\" offset=0x3 size=0x2\r\nnoop\r\n");
+}
+
+#[test]
+fn test_download_truncated() {
+    use client::mock_client;
+
+    let (mut client, _server) = mock_client(b"203- binary response follows\r\n\x0a\x00\x00\x00abc");
+    let mut data = Vec::new();
+    let err = client.download(r"E:\a.txt", &mut data).unwrap_err();
+    assert!(err.is_fatal());
+    // The connection is broken, but using it again must fail rather than
+    // panic.
+    assert!(client.execute(StatusCode::Ok, "noop").is_err());
+}
+
+#[test]
+fn test_download_writer_error() {
+    use client::mock_client;
+
+    struct Failing;
+    impl Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let (mut client, server) = mock_client(b"\
+        203- binary response follows\r\n\x03\x00\x00\x00abc\
+        200- OK\r\n");
+    let err = client.download(r"E:\a.txt", &mut Failing).unwrap_err();
+    assert!(!err.is_fatal());
+    client.execute(StatusCode::Ok, "noop").unwrap().finish().unwrap();
+    drop(client);
+    assert!(server.join().unwrap().ends_with(b"noop\r\n"));
+}
+
+#[test]
+fn test_upload() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"\
+        204- send binary data\r\n200- OK\r\n\
+        413- file cannot be created\r\n\
+        204- send binary data\r\n415- disk full\r\n200- OK\r\n\
+        204- send binary data\r\n200- OK\r\n410- file already exists\r\n\
+        200- OK\r\n200- OK\r\n200- OK\r\n\
+        204- send binary data\r\n200- OK\r\n200- OK\r\n");
+    client.upload(&mut &b"hello"[..], r"E:\a.txt", 5).unwrap();
+    let err = client.upload(&mut &b"hello"[..], r"E:\a.txt", 5).unwrap_err();
+    assert_eq!(err.category(), Some(ErrorCategory::PermissionDenied));
+
+    let upload = |client: &mut Client, reports: &mut Vec<u64>| {
+        Upload::new(r"E:\a.xex")
+            .with_temp_name()
+            .with_progress(|p| reports.push(p.transferred))
+            .run(client, &mut &b"abc"[..], 3)
+    };
+    let mut reports = Vec::new();
+    let err = upload(&mut client, &mut reports).unwrap_err();
+    assert_eq!(err.category(), Some(ErrorCategory::StorageFull));
+    upload(&mut client, &mut reports).unwrap();
+    assert_eq!(reports, vec![3, 3]);
+
+    // The reader ends early, so the padded file is deleted.
+    let err = client.upload(&mut &b"ab"[..], r"E:\b.txt", 4).unwrap_err();
+    assert!(!err.is_fatal());
+    match *err.kind() {
+        ::error::ErrorKind::Local(ref e) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+        ref kind => panic!("unexpected error kind: {:?}", kind),
+    }
+
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert_eq!(sent, "\
+        sendfile name=\"E:\\a.txt\" length=0x5\r\nhello\
+        sendfile name=\"E:\\a.txt\" length=0x5\r\n\
+        sendfile name=\"E:\\a.xex.part\" length=0x3\r\nabc\
+        delete name=\"E:\\a.xex.part\"\r\n\
+        sendfile name=\"E:\\a.xex.part\" length=0x3\r\nabc\
+        rename name=\"E:\\a.xex.part\" newname=\"E:\\a.xex\"\r\n\
+        rename name=\"E:\\a.xex\" newname=\"E:\\a.xex.old\"\r\n\
+        rename name=\"E:\\a.xex.part\" newname=\"E:\\a.xex\"\r\n\
+        delete name=\"E:\\a.xex.old\"\r\n\
+        sendfile name=\"E:\\b.txt\" length=0x4\r\nab\0\0\
+        delete name=\"E:\\b.txt\"\r\n");
+}
+
+#[test]
+fn test_suffixed_name() {
+    assert_eq!(suffixed_name(r"E:\Games\a.xex", TEMP_SUFFIX), r"E:\Games\a.xex.part");
+    let long = "a".repeat(40);
+    assert_eq!(suffixed_name(&format!(r"E:\Games\{}", long), TEMP_SUFFIX),
+               format!(r"E:\Games\{}.part", &long[..37]));
+    assert_eq!(suffixed_name("b.bin", OLD_SUFFIX), "b.bin.old");
+}
+
+#[test]
+fn test_resumable_upload() {
+    use client::mock_client;
+    use std::io::Cursor;
+
+    let (mut client, server) = mock_client(b"\
+        202- multiline response follows\r\nsizehi=0x0 sizelo=0x4 createhi=0x0 createlo=0x0 \
+        changehi=0x0 changelo=0x0\r\n.\r\n\
+        204- send binary data\r\n200- OK\r\n\
+        204- send binary data\r\n200- OK\r\n200- OK\r\n\
+        202- multiline response follows\r\nsizehi=0x0 sizelo=0x4 createhi=0x0 createlo=0x0 \
+        changehi=0x0 changelo=0x0\r\n.\r\n\
+        203- binary response follows\r\n\x04\x00\x00\x00abXX\
+        204- send binary data\r\n200- OK\r\n200- OK\r\n\
+        402- file not found\r\n204- send binary data\r\n200- OK\r\n\
+        204- send binary data\r\n200- OK\r\n200- OK\r\n");
+    let mut file = Cursor::new(b"abcdefg".to_vec());
+    let mut reports = Vec::new();
+    let resumed = ResumableUpload::new(r"E:\a.bin")
+        .with_chunk_size(2)
+        .with_progress(|p| reports.push(p.transferred))
+        .run(&mut client, &mut file, 7)
+        .unwrap();
+    assert_eq!(resumed, 4);
+    assert_eq!(reports, vec![6, 7]);
+
+    let resumed = ResumableUpload::new(r"E:\a.bin").with_verify()
+        .run(&mut client, &mut file, 7).unwrap();
+    assert_eq!(resumed, 2);
+    assert_eq!(client.upload_resumable(&mut file, r"E:\b.bin", 7).unwrap(), 0);
+
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert_eq!(sent, "\
+        getfileattributes name=\"E:\\a.bin\"\r\n\
+        writefile name=\"E:\\a.bin\" offset=0x4 length=0x2\r\nef\
+        writefile name=\"E:\\a.bin\" offset=0x6 length=0x1\r\ng\
+        fileeof name=\"E:\\a.bin\" size=0x7\r\n\
+        getfileattributes name=\"E:\\a.bin\"\r\n\
+        getfile name=\"E:\\a.bin\" offset=0x0 size=0x4\r\n\
+        writefile name=\"E:\\a.bin\" offset=0x2 length=0x5\r\ncdefg\
+        fileeof name=\"E:\\a.bin\" size=0x7\r\n\
+        getfileattributes name=\"E:\\b.bin\"\r\n\
+        sendfile name=\"E:\\b.bin\" length=0x0\r\n\
+        writefile name=\"E:\\b.bin\" offset=0x0 length=0x7\r\nabcdefg\
+        fileeof name=\"E:\\b.bin\" size=0x7\r\n");
+}
@@ -0,0 +1,298 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use client::Client;
+use error::{Error, ErrorKind, Result};
+use memory::MAX_READ_CHUNK;
+use status::StatusCode;
+use xbox::{Endian, Platform};
+
+/// Number of bytes fetched at a time while looking for a string terminator.
+const STRING_CHUNK: usize = 64;
+
+/// A fixed-size value that can be read from and written to console memory.
+pub trait MemoryValue: Copy {
+    /// The size of the value in bytes.
+    const SIZE: usize;
+
+    /// Decodes a value from exactly `SIZE` bytes.
+    fn from_bytes(bytes: &[u8], endian: Endian) -> Self;
+
+    /// Encodes the value into exactly `SIZE` bytes.
+    fn to_bytes(self, endian: Endian) -> Vec<u8>;
+}
+
+macro_rules! memory_value {
+    ($($t:ty),*) => {$(
+        impl MemoryValue for $t {
+            const SIZE: usize = ::std::mem::size_of::<$t>();
+
+            fn from_bytes(bytes: &[u8], endian: Endian) -> $t {
+                let mut buf = [0; ::std::mem::size_of::<$t>()];
+                buf.copy_from_slice(bytes);
+                match endian {
+                    Endian::Little => <$t>::from_le_bytes(buf),
+                    Endian::Big => <$t>::from_be_bytes(buf),
+                }
+            }
+
+            fn to_bytes(self, endian: Endian) -> Vec<u8> {
+                match endian {
+                    Endian::Little => self.to_le_bytes().to_vec(),
+                    Endian::Big => self.to_be_bytes().to_vec(),
+                }
+            }
+        }
+    )*}
+}
+
+memory_value!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// The type of a value watched or compared at runtime.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    U8, U16, U32, U64,
+    I8, I16, I32, I64,
+    F32, F64,
+}
+
+impl ValueType {
+    /// The size of a value of this type in bytes.
+    pub fn size(&self) -> usize {
+        match *self {
+            ValueType::U8 | ValueType::I8 => 1,
+            ValueType::U16 | ValueType::I16 => 2,
+            ValueType::U32 | ValueType::I32 | ValueType::F32 => 4,
+            ValueType::U64 | ValueType::I64 | ValueType::F64 => 8,
+        }
+    }
+
+    /// Decodes a value from exactly `size()` bytes.
+    pub fn decode(&self, bytes: &[u8], endian: Endian) -> Value {
+        match *self {
+            ValueType::U8 => Value::U8(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::U16 => Value::U16(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::U32 => Value::U32(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::U64 => Value::U64(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::I8 => Value::I8(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::I16 => Value::I16(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::I32 => Value::I32(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::I64 => Value::I64(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::F32 => Value::F32(MemoryValue::from_bytes(bytes, endian)),
+            ValueType::F64 => Value::F64(MemoryValue::from_bytes(bytes, endian)),
+        }
+    }
+}
+
+/// A value whose type is chosen at runtime.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    U8(u8), U16(u16), U32(u32), U64(u64),
+    I8(i8), I16(i16), I32(i32), I64(i64),
+    F32(f32), F64(f64),
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match *self {
+            Value::U8(_) => ValueType::U8,
+            Value::U16(_) => ValueType::U16,
+            Value::U32(_) => ValueType::U32,
+            Value::U64(_) => ValueType::U64,
+            Value::I8(_) => ValueType::I8,
+            Value::I16(_) => ValueType::I16,
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+        }
+    }
+
+    pub fn to_bytes(self, endian: Endian) -> Vec<u8> {
+        match self {
+            Value::U8(v) => v.to_bytes(endian),
+            Value::U16(v) => v.to_bytes(endian),
+            Value::U32(v) => v.to_bytes(endian),
+            Value::U64(v) => v.to_bytes(endian),
+            Value::I8(v) => v.to_bytes(endian),
+            Value::I16(v) => v.to_bytes(endian),
+            Value::I32(v) => v.to_bytes(endian),
+            Value::I64(v) => v.to_bytes(endian),
+            Value::F32(v) => v.to_bytes(endian),
+            Value::F64(v) => v.to_bytes(endian),
+        }
+    }
+}
+
+impl Client {
+    /// The byte order of the connected console's memory.
+    ///
+    /// If the platform is not known from the port connected to, it is
+    /// detected with `consoletype`, which only the Xbox 360 understands, and
+    /// remembered.
+    pub fn endian(&mut self) -> Result<Endian> {
+        if let Some(platform) = self.platform() {
+            return Ok(platform.endian());
+        }
+        let platform = match self.execute(StatusCode::Ok, "consoletype").and_then(|e| e.finish()) {
+            Ok(_) => Platform::Xbox360,
+            Err(ref e) if matches!(*e.kind(),
+                ErrorKind::CommandFailed(StatusCode::UnknownCommand, _)) => Platform::Classic,
+            Err(e) => return Err(e),
+        };
+        self.set_platform(Some(platform));
+        Ok(platform.endian())
+    }
+
+    /// Reads a value in the console's byte order.
+    pub fn read_value<T: MemoryValue>(&mut self, addr: u32) -> Result<T> {
+        let endian = self.endian()?;
+        let bytes = self.read_memory(addr, T::SIZE)?;
+        Ok(T::from_bytes(&bytes, endian))
+    }
+
+    /// Writes a value in the console's byte order.
+    pub fn write_value<T: MemoryValue>(&mut self, addr: u32, value: T) -> Result<()> {
+        let endian = self.endian()?;
+        self.write_memory_all(addr, &value.to_bytes(endian))
+    }
+
+    pub fn read_u8(&mut self, addr: u32) -> Result<u8> { self.read_value(addr) }
+    pub fn read_u16(&mut self, addr: u32) -> Result<u16> { self.read_value(addr) }
+    pub fn read_u32(&mut self, addr: u32) -> Result<u32> { self.read_value(addr) }
+    pub fn read_u64(&mut self, addr: u32) -> Result<u64> { self.read_value(addr) }
+    pub fn read_f32(&mut self, addr: u32) -> Result<f32> { self.read_value(addr) }
+    pub fn read_f64(&mut self, addr: u32) -> Result<f64> { self.read_value(addr) }
+
+    /// Reads a pointer. Both platforms use 32-bit pointers.
+    pub fn read_ptr(&mut self, addr: u32) -> Result<u32> { self.read_value(addr) }
+
+    pub fn write_u8(&mut self, addr: u32, value: u8) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_u16(&mut self, addr: u32, value: u16) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_u32(&mut self, addr: u32, value: u32) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_u64(&mut self, addr: u32, value: u64) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_f32(&mut self, addr: u32, value: f32) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_f64(&mut self, addr: u32, value: f64) -> Result<()> { self.write_value(addr, value) }
+    pub fn write_ptr(&mut self, addr: u32, value: u32) -> Result<()> { self.write_value(addr, value) }
+
+    /// Reads a null-terminated 8-bit string of at most `max_len` bytes, not
+    /// counting the terminator. Bytes are decoded as Latin-1, so ASCII is
+    /// returned unchanged.
+    pub fn read_string(&mut self, addr: u32, max_len: usize) -> Result<String> {
+        let bytes = self.read_terminated(addr, max_len, 1)?;
+        Ok(bytes.iter().map(|&b| b as char).collect())
+    }
+
+    /// Reads a null-terminated UTF-16 string of at most `max_len` code units,
+    /// not counting the terminator, in the console's byte order.
+    pub fn read_utf16_string(&mut self, addr: u32, max_len: usize) -> Result<String> {
+        let endian = self.endian()?;
+        let bytes = self.read_terminated(addr, max_len, 2)?;
+        let units = bytes.chunks(2).map(|b| u16::from_bytes(b, endian)).collect::<Vec<_>>();
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    /// Writes `s` followed by a null terminator as an 8-bit string. Fails
+    /// with `InvalidInput` if `s` has characters outside Latin-1.
+    pub fn write_string(&mut self, addr: u32, s: &str) -> Result<()> {
+        let mut bytes = Vec::with_capacity(s.len() + 1);
+        for c in s.chars() {
+            if c as u32 > 0xff {
+                return Err(Error::io_custom(io::ErrorKind::InvalidInput,
+                    format!("'{}' cannot be written as an 8-bit character", c),
+                    format!("setmem addr=0x{:08x}", addr)));
+            }
+            bytes.push(c as u8);
+        }
+        bytes.push(0);
+        self.write_memory_all(addr, &bytes)
+    }
+
+    /// Writes `s` followed by a null terminator as UTF-16 in the console's
+    /// byte order.
+    pub fn write_utf16_string(&mut self, addr: u32, s: &str) -> Result<()> {
+        let endian = self.endian()?;
+        let mut bytes = Vec::with_capacity((s.len() + 1) * 2);
+        for unit in s.encode_utf16().chain(Some(0)) {
+            bytes.extend_from_slice(&unit.to_bytes(endian));
+        }
+        self.write_memory_all(addr, &bytes)
+    }
+
+    /// Reads units of `unit` bytes until a zero unit, at most `max_len` units
+    /// or the first unmapped byte, whichever comes first.
+    fn read_terminated(&mut self, addr: u32, max_len: usize, unit: usize) -> Result<Vec<u8>> {
+        let max_bytes = max_len * unit;
+        let mut bytes = Vec::new();
+        while bytes.len() < max_bytes {
+            let pos = addr.wrapping_add(bytes.len() as u32);
+            // Stop each chunk at a page boundary so that reading past the end
+            // of a string does not touch the next page unless it has to.
+            let to_page = MAX_READ_CHUNK - (pos as usize % MAX_READ_CHUNK);
+            // Always read whole units, even if one straddles the boundary.
+            let len = STRING_CHUNK.min(to_page).min(max_bytes - bytes.len())
+                .div_ceil(unit) * unit;
+            let block = self.read_memory_block(pos, len)?;
+            let mapped = block.mapped().iter().take_while(|&&m| m).count();
+            for chunk in block.data()[..mapped - mapped % unit].chunks(unit) {
+                if chunk.iter().all(|&b| b == 0) {
+                    return Ok(bytes);
+                }
+                bytes.extend_from_slice(chunk);
+            }
+            if mapped < len {
+                let message = StatusCode::MemoryNotMapped.default_message().unwrap_or("");
+                return Err(Error::command_failed(StatusCode::MemoryNotMapped, message,
+                    format!("getmem addr=0x{:08x}", pos.wrapping_add(mapped as u32))));
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+#[test]
+fn test_typed_values() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"200- devkit\r\n\
+        203- binary response follows\r\n\x04\x00\x12\x34\x56\x78\
+        200- set 4 bytes\r\n\
+        203- binary response follows\r\n\x06\x00\x00\x48\x00\x69\x00\x00");
+    assert_eq!(client.read_u32(0x1000).unwrap(), 0x12345678);
+    assert_eq!(client.platform(), Some(Platform::Xbox360));
+    client.write_f32(0x1000, 1.0).unwrap();
+    assert_eq!(client.read_utf16_string(0x2000, 3).unwrap(), "Hi");
+    drop(client);
+    assert_eq!(server.join().unwrap(), &b"consoletype\r\n\
+        getmemex addr=0x00001000 length=0x4\r\n\
+        setmem addr=0x00001000 data=3f800000\r\n\
+        getmemex addr=0x00002000 length=0x6\r\n"[..]);
+}
+
+#[test]
+fn test_utf16_string_across_page() {
+    use client::mock_client;
+
+    let (mut client, server) = mock_client(b"\
+        202- multiline response follows\r\n4800\r\n.\r\n\
+        202- multiline response follows\r\n69000000\r\n.\r\n");
+    client.set_platform(Some(Platform::Classic));
+    assert_eq!(client.read_utf16_string(0x1fff, 3).unwrap(), "Hi");
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert!(sent.starts_with("getmem addr=0x00001fff length=0x2\r\n\
+                              getmem addr=0x00002001 length=0x4\r\n"));
+}
+
+#[test]
+fn test_memory_value() {
+    assert_eq!(0x1234u16.to_bytes(Endian::Little), vec![0x34, 0x12]);
+    assert_eq!(u32::from_bytes(&[0, 0, 0, 1], Endian::Big), 1);
+    assert_eq!(f64::from_bytes(&2.5f64.to_bytes(Endian::Big), Endian::Big), 2.5);
+}
@@ -0,0 +1,242 @@
+// Copyright 2017 xbdm-rs Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use client::Client;
+use error::Result;
+use values::{Value, ValueType};
+
+const DEFAULT_INTERVAL_MILLIS: u64 = 100;
+
+/// A change noticed by a [`Watcher`].
+///
+/// [`Watcher`]: struct.Watcher.html
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchEvent {
+    /// The value at `addr` changed. `None` means the memory was not mapped.
+    /// The first poll reports every watched value with an `old` of `None`.
+    Changed { addr: u32, old: Option<Value>, new: Option<Value> },
+    /// A frozen value had drifted to `found` and was written back.
+    Restored { addr: u32, found: Value },
+}
+
+#[derive(Clone, Debug)]
+struct Watch {
+    addr: u32,
+    ty: ValueType,
+    freeze: Option<Value>,
+    last: Option<Vec<u8>>,
+    polled: bool,
+}
+
+/// Polls typed values in console memory and reports changes, optionally
+/// writing frozen values back whenever they drift.
+///
+/// A watcher can be polled by hand with [`poll`], or moved to a background
+/// thread with its own connection by [`spawn`].
+///
+/// [`poll`]: #method.poll
+/// [`spawn`]: #method.spawn
+#[derive(Clone, Debug)]
+pub struct Watcher {
+    watches: Vec<Watch>,
+    interval: Duration,
+}
+
+impl Watcher {
+    /// Creates a watcher that polls every 100 milliseconds.
+    pub fn new() -> Watcher {
+        Watcher {
+            watches: Vec::new(),
+            interval: Duration::from_millis(DEFAULT_INTERVAL_MILLIS),
+        }
+    }
+
+    /// Sets how often [`spawn`] polls.
+    ///
+    /// [`spawn`]: #method.spawn
+    pub fn with_interval(mut self, interval: Duration) -> Watcher {
+        self.interval = interval;
+        self
+    }
+
+    /// Reports changes to the value of type `ty` at `addr`.
+    pub fn watch(mut self, addr: u32, ty: ValueType) -> Watcher {
+        self.watches.push(Watch { addr, ty, freeze: None, last: None, polled: false });
+        self
+    }
+
+    /// Keeps `value` at `addr`, writing it back whenever it changes.
+    pub fn freeze(mut self, addr: u32, value: Value) -> Watcher {
+        self.watches.push(Watch {
+            addr,
+            ty: value.value_type(),
+            freeze: Some(value),
+            last: None,
+            polled: false,
+        });
+        self
+    }
+
+    pub fn interval(&self) -> Duration { self.interval }
+
+    /// Reads every watched value once and returns what changed.
+    pub fn poll(&mut self, client: &mut Client) -> Result<Vec<WatchEvent>> {
+        let endian = client.endian()?;
+        let mut events = Vec::new();
+        for watch in &mut self.watches {
+            let block = client.read_memory_block(watch.addr, watch.ty.size())?;
+            let mut current = if block.is_fully_mapped() {
+                Some(block.into_data())
+            } else {
+                None
+            };
+            if let (Some(freeze), Some(bytes)) = (watch.freeze, current.as_ref()) {
+                let frozen = freeze.to_bytes(endian);
+                if *bytes != frozen {
+                    events.push(WatchEvent::Restored {
+                        addr: watch.addr,
+                        found: watch.ty.decode(bytes, endian),
+                    });
+                    client.write_memory_all(watch.addr, &frozen)?;
+                    current = Some(frozen);
+                }
+            }
+            if !watch.polled || current != watch.last {
+                events.push(WatchEvent::Changed {
+                    addr: watch.addr,
+                    old: watch.last.as_ref().map(|b| watch.ty.decode(b, endian)),
+                    new: current.as_ref().map(|b| watch.ty.decode(b, endian)),
+                });
+            }
+            watch.last = current;
+            watch.polled = true;
+        }
+        Ok(events)
+    }
+
+    /// Polls on a background thread over `client`, which should be a
+    /// connection dedicated to the watcher, until the handle is stopped or
+    /// dropped.
+    pub fn spawn(mut self, mut client: Client) -> WatchHandle {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let next = Instant::now() + self.interval;
+                    for event in self.poll(&mut client)? {
+                        if tx.send(event).is_err() {
+                            return Ok(client);
+                        }
+                    }
+                    let now = Instant::now();
+                    if next > now {
+                        thread::park_timeout(next - now);
+                    }
+                }
+                Ok(client)
+            })
+        };
+        WatchHandle { events: rx, stop, thread: Some(thread) }
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Watcher {
+        Watcher::new()
+    }
+}
+
+/// A [`Watcher`] running on a background thread.
+///
+/// Dropping the handle stops the watcher without waiting for it; the thread
+/// closes its connection once any poll in progress has finished.
+///
+/// [`Watcher`]: struct.Watcher.html
+#[derive(Debug)]
+pub struct WatchHandle {
+    events: Receiver<WatchEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<Result<Client>>>,
+}
+
+impl WatchHandle {
+    /// The events reported by the watcher. The channel is closed when the
+    /// watcher stops, including after an error.
+    pub fn events(&self) -> &Receiver<WatchEvent> { &self.events }
+
+    /// Stops the watcher and returns its connection, or the error that
+    /// stopped it.
+    pub fn stop(mut self) -> Result<Client> {
+        self.signal_stop();
+        let thread = self.thread.take().expect("watcher thread already joined");
+        match thread.join() {
+            Ok(result) => result,
+            Err(e) => ::std::panic::resume_unwind(e),
+        }
+    }
+
+    fn signal_stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(ref thread) = self.thread {
+            thread.thread().unpark();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.signal_stop();
+    }
+}
+
+#[test]
+fn test_watcher() {
+    use client::mock_client;
+    use xbox::Platform;
+
+    let (mut client, server) = mock_client(b"\
+        202- multiline response follows\r\n0a00\r\n.\r\n\
+        202- multiline response follows\r\n05000000\r\n.\r\n200- set 4 bytes\r\n\
+        202- multiline response follows\r\n0b00\r\n.\r\n\
+        202- multiline response follows\r\n63000000\r\n.\r\n");
+    client.set_platform(Some(Platform::Classic));
+    let mut watcher = Watcher::new()
+        .watch(0x1000, ValueType::U16)
+        .freeze(0x2000, Value::I32(99));
+    assert_eq!(watcher.poll(&mut client).unwrap(), vec![
+        WatchEvent::Changed { addr: 0x1000, old: None, new: Some(Value::U16(10)) },
+        WatchEvent::Restored { addr: 0x2000, found: Value::I32(5) },
+        WatchEvent::Changed { addr: 0x2000, old: None, new: Some(Value::I32(99)) },
+    ]);
+    assert_eq!(watcher.poll(&mut client).unwrap(), vec![
+        WatchEvent::Changed { addr: 0x1000, old: Some(Value::U16(10)), new: Some(Value::U16(11)) },
+    ]);
+    drop(client);
+    let sent = String::from_utf8(server.join().unwrap()).unwrap();
+    assert!(sent.contains("setmem addr=0x00002000 data=63000000\r\n"));
+}
+
+#[test]
+fn test_watcher_drop() {
+    use client::mock_client;
+    use xbox::Platform;
+
+    let (mut client, server) = mock_client(b"");
+    client.set_platform(Some(Platform::Classic));
+    let handle = Watcher::new().with_interval(Duration::from_secs(3600)).spawn(client);
+    drop(handle);
+    // The server only returns once the watcher thread has dropped the client.
+    server.join().unwrap();
+}
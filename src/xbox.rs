@@ -3,28 +3,100 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
 use std::option;
 use std::time::Duration;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::{MAX_NAME_LENGTH, PORT_360, PORT_CLASSIC};
 
 const RESOLVE_TIMEOUT_MILLIS: u64 = 300;
-const MAX_PACKET_LENGTH: usize = MAX_NAME_LENGTH + 2;
+pub const MAX_PACKET_LENGTH: usize = MAX_NAME_LENGTH + 2 + RELAY_TRAILER_LENGTH;
+
+/// Byte order used by a console's CPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Endian {
+    /// Least significant byte first (x86).
+    Little,
+    /// Most significant byte first (PowerPC).
+    Big,
+}
+
+/// The hardware platform of an Xbox Development Kit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Platform {
+    /// The original Xbox.
+    Classic,
+    /// The Xbox 360.
+    Xbox360,
+}
+
+impl Platform {
+    /// Returns the platform whose XBDM service listens on `port` by default.
+    pub fn from_port(port: u16) -> Option<Platform> {
+        match port {
+            PORT_CLASSIC => Some(Platform::Classic),
+            PORT_360 => Some(Platform::Xbox360),
+            _ => None,
+        }
+    }
+
+    /// The TCP/UDP port number used by XBDM on this platform.
+    pub fn default_port(&self) -> u16 {
+        match *self {
+            Platform::Classic => PORT_CLASSIC,
+            Platform::Xbox360 => PORT_360,
+        }
+    }
+
+    /// The byte order of this platform's memory.
+    pub fn endian(&self) -> Endian {
+        match *self {
+            Platform::Classic => Endian::Little,
+            Platform::Xbox360 => Endian::Big,
+        }
+    }
+}
 
 /// Describes an Xbox Development Kit found by a discover or resolve operation.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Xbox {
     ip: Ipv4Addr,
     port: u16,
+    platform: Platform,
     name: String,
 }
 
 impl Xbox {
+    /// Describes the console at `ip` without contacting it. The port is the
+    /// platform's default; use [`with_port`] if it has been remapped.
+    ///
+    /// [`with_port`]: #method.with_port
+    pub fn new<N: Into<String>>(ip: Ipv4Addr, platform: Platform, name: N) -> Xbox {
+        Xbox {
+            ip,
+            port: platform.default_port(),
+            platform,
+            name: name.into(),
+        }
+    }
+
+    /// Replaces the port used to connect to XBDM.
+    pub fn with_port(mut self, port: u16) -> Xbox {
+        self.port = port;
+        self
+    }
+
     pub fn socket_addr(&self) -> SocketAddr {
         SocketAddr::new(IpAddr::V4(self.ip), self.port)
     }
     pub fn ip(&self) -> Ipv4Addr { self.ip }
     pub fn port(&self) -> u16 { self.port }
+    pub fn platform(&self) -> Platform { self.platform }
     pub fn name(&self) -> &str { &self.name }
-    pub fn is_360(&self) -> bool { self.port == PORT_360 }
-    pub fn is_classic(&self) -> bool { self.port == PORT_CLASSIC }
+    pub fn is_360(&self) -> bool { self.platform == Platform::Xbox360 }
+    pub fn is_classic(&self) -> bool { self.platform == Platform::Classic }
 }
 
 impl ToSocketAddrs for Xbox {
@@ -34,20 +106,30 @@ impl ToSocketAddrs for Xbox {
     }
 }
 
-fn parse_reply(data: &[u8], src: SocketAddr) -> Option<Xbox> {
+/// Length of the address that a relay appends to a reply.
+pub const RELAY_TRAILER_LENGTH: usize = 4;
+
+/// Parses a NAP reply from `src`. Replies forwarded by a relay carry the
+/// console's real address after the name, since `src` is the relay.
+pub fn parse_reply(data: &[u8], src: SocketAddr) -> Option<Xbox> {
     if data.len() < 3 || data[0] != 2 || data[1] == 0 {
         return None
     }
-    if src.port() != PORT_360 && src.port() != PORT_CLASSIC {
+    let end = (data[1] as usize) + 2;
+    if data.len() < end {
         return None
     }
+    let platform = Platform::from_port(src.port())?;
+    let ip = match (&data[end..], src.ip()) {
+        (t, _) if t.len() == RELAY_TRAILER_LENGTH => Ipv4Addr::new(t[0], t[1], t[2], t[3]),
+        (_, IpAddr::V4(ip)) => ip,
+        _ => return None,
+    };
     Some(Xbox {
-        ip: match src.ip() {
-            IpAddr::V4(ip) => ip,
-            _ => return None,
-        },
+        ip,
         port: src.port(),
-        name: match String::from_utf8(data[2..(data[1] as usize) + 2].into()) {
+        platform,
+        name: match String::from_utf8(data[2..end].into()) {
             Ok(s) => s,
             Err(_) => return None,
         },
@@ -88,16 +170,7 @@ pub fn discover() -> io::Result<Discover> {
     socket.set_write_timeout(timeout)?;
     socket.send_to(&pkt, (ip, PORT_360))?;
     socket.send_to(&pkt, (ip, PORT_CLASSIC))?;
-    Ok(Discover { socket: socket })
-}
-
-/// Resolve the Xbox debug name or IP address specified by `host`
-/// as an `Xbox` instance.
-pub fn resolve(host: &str) -> io::Result<Xbox> {
-    match host.parse() {
-        Ok(ip) => resolve_ip(ip),
-        _ => resolve_name(host),
-    }
+    Ok(Discover { socket })
 }
 
 /// Resolve the IP address specified by `ip` as an `Xbox` instance.
@@ -127,7 +200,7 @@ pub fn resolve_ip(ip: Ipv4Addr) -> io::Result<Xbox> {
 
 /// Resolve the Xbox debug name specified by `name` as an `Xbox` instance.
 pub fn resolve_name(name: &str) -> io::Result<Xbox> {
-    if name.len() == 0 {
+    if name.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput, "name is too short"));
     } else if name.len() > MAX_NAME_LENGTH {
@@ -141,14 +214,15 @@ pub fn resolve_name(name: &str) -> io::Result<Xbox> {
     socket.set_read_timeout(timeout)?;
     socket.set_write_timeout(timeout)?;
 
-    let mut buf = &mut [0; MAX_PACKET_LENGTH][..name.len()+2];
+    let mut buf = [0; MAX_PACKET_LENGTH];
+    let len = name.len() + 2;
     buf[0] = 1;
     buf[1] = name.len() as u8;
-    buf[2..].copy_from_slice(name.as_bytes());
+    buf[2..len].copy_from_slice(name.as_bytes());
 
     let ip = Ipv4Addr::new(255, 255, 255, 255);
-    socket.send_to(&buf, (ip, PORT_360))?;
-    socket.send_to(&buf, (ip, PORT_CLASSIC))?;
+    socket.send_to(&buf[..len], (ip, PORT_360))?;
+    socket.send_to(&buf[..len], (ip, PORT_CLASSIC))?;
 
     loop {
         let (n, src) = match socket.recv_from(&mut buf) {
@@ -163,3 +237,19 @@ pub fn resolve_name(name: &str) -> io::Result<Xbox> {
         }
     }
 }
+
+#[test]
+fn test_parse_reply() {
+    let src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)), PORT_360);
+    let xbox = parse_reply(&[2, 6, b'M', b'Y', b'X', b'B', b'O', b'X'], src).unwrap();
+    assert_eq!(xbox, Xbox::new(Ipv4Addr::new(10, 0, 0, 5), Platform::Xbox360, "MYXBOX"));
+    assert_eq!(xbox.platform().endian(), Endian::Big);
+
+    let src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), PORT_CLASSIC);
+    let xbox = parse_reply(&[2, 1, b'X', 10, 1, 0, 7], src).unwrap();
+    assert_eq!(xbox, Xbox::new(Ipv4Addr::new(10, 1, 0, 7), Platform::Classic, "X"));
+    assert!(parse_reply(&[2, 6, b'X'], src).is_none());
+
+    let src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)), 1234);
+    assert!(parse_reply(&[2, 1, b'X'], src).is_none());
+}